@@ -0,0 +1,83 @@
+extern crate toybox;
+extern crate toybox_core;
+
+use std::time::Instant;
+use toybox::graphics::{Canvas, Color, Drawable, GrayscaleBuffer, ImageBuffer, Tag};
+
+const USAGE: &str = "usage: bench_render [N_FRAMES=2000] [REPEATS=5]
+Records N_FRAMES of draw commands from every game, then reports rendering frames/second
+for the span-filling rasterizer (which also reuses each sprite's cached RGBA and grayscale
+renders) and for the old pixel-by-pixel one. Build with --release.";
+
+/// Forwards only the required Canvas methods, so rectangles and sprites go pixel by pixel through the provided methods, as every buffer did before they filled whole rows and cached sprites.
+struct PerPixel<C: Canvas>(C);
+
+impl<C: Canvas> Canvas for PerPixel<C> {
+    fn size(&self) -> (i32, i32) {
+        self.0.size()
+    }
+    fn clear(&mut self, color: Color, tag: Tag) {
+        self.0.clear(color, tag)
+    }
+    fn put_pixel(&mut self, x: i32, y: i32, color: Color, tag: Tag) {
+        self.0.put_pixel(x, y, color, tag)
+    }
+}
+
+/// Frames per second to render every frame `repeats` times onto `canvas`.
+fn frames_per_second<C: Canvas>(canvas: &mut C, frames: &[Vec<Drawable>], repeats: usize) -> f64 {
+    let start = Instant::now();
+    for _ in 0..repeats {
+        for frame in frames {
+            canvas.render(frame);
+        }
+    }
+    let elapsed = start.elapsed();
+    let seconds = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) * 1e-9;
+    (frames.len() * repeats) as f64 / seconds
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let n_frames: usize = args.get(1).map_or(2000, |n| n.parse().expect(USAGE));
+    let repeats: usize = args.get(2).map_or(5, |n| n.parse().expect(USAGE));
+
+    println!(
+        "{:<16} {:>14} {:>14} {:>8} {:>14} {:>14} {:>8}",
+        "game", "rgba/pixel", "rgba/span", "speedup", "gray/pixel", "gray/span", "speedup"
+    );
+    for game in toybox::GAME_LIST {
+        let mut sim = toybox::get_simulation_by_name(game).unwrap();
+        let (w, h) = sim.game_size();
+        let actions = sim.legal_action_set();
+        let mut state = sim.new_game();
+        let mut frames = Vec::with_capacity(n_frames);
+        for i in 0..n_frames {
+            if state.lives() < 0 {
+                state = sim.new_game();
+            }
+            state.update_mut(actions[i % actions.len()].to_input());
+            frames.push(state.draw());
+        }
+
+        let rgba_pixel =
+            frames_per_second(&mut PerPixel(ImageBuffer::alloc(w, h)), &frames, repeats);
+        let rgba_span = frames_per_second(&mut ImageBuffer::alloc(w, h), &frames, repeats);
+        let gray_pixel = frames_per_second(
+            &mut PerPixel(GrayscaleBuffer::alloc(w, h)),
+            &frames,
+            repeats,
+        );
+        let gray_span = frames_per_second(&mut GrayscaleBuffer::alloc(w, h), &frames, repeats);
+        println!(
+            "{:<16} {:>14.0} {:>14.0} {:>7.2}x {:>14.0} {:>14.0} {:>7.2}x",
+            game,
+            rgba_pixel,
+            rgba_span,
+            rgba_span / rgba_pixel,
+            gray_pixel,
+            gray_span,
+            gray_span / gray_pixel
+        );
+    }
+}
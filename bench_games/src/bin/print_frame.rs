@@ -0,0 +1,61 @@
+extern crate toybox;
+extern crate toybox_core;
+
+use std::process;
+use toybox::graphics::{render_text, SvgOptions, SvgRenderer, TextStyle};
+use toybox::random;
+
+const USAGE: &str =
+    "usage: print_frame GAME [ansi|ascii|symbolic|svg|svg-annotated] [N_STEPS=0] [SEED=0] [COLUMNS=80]
+Plays GAME with uniformly random legal actions for N_STEPS and prints the final frame:
+in color (ansi), as ASCII shading (ascii), as the game's own text dump (symbolic),
+or as an SVG image, optionally with every object outlined and labelled (svg-annotated).";
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    }
+    let game = &args[1];
+    let mode = args.get(2).map_or("ansi", |m| m.as_str());
+    let n_steps: usize = args.get(3).map_or(0, |n| n.parse().expect(USAGE));
+    let seed: u32 = args.get(4).map_or(0, |n| n.parse().expect(USAGE));
+    let columns: i32 = args.get(5).map_or(80, |n| n.parse().expect(USAGE));
+
+    let mut sim = toybox::get_simulation_by_name(game).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    sim.reset_seed(seed);
+    let actions = sim.legal_action_set();
+    let mut rand = random::Gen::new_from_seed(seed);
+    let mut state = sim.new_game();
+    for _ in 0..n_steps {
+        if state.lives() < 0 {
+            break;
+        }
+        let action = actions[rand._next_u32() as usize % actions.len()];
+        state.update_mut(action.to_input());
+    }
+
+    let text = match mode {
+        "ansi" => render_text(&state.draw(), sim.game_size(), columns, TextStyle::Ansi),
+        "ascii" => render_text(&state.draw(), sim.game_size(), columns, TextStyle::Ascii),
+        "symbolic" => state.to_text(),
+        "svg" | "svg-annotated" => {
+            let (w, h) = sim.game_size();
+            let options = SvgOptions {
+                annotate: mode == "svg-annotated",
+                kinds: sim.object_kinds(),
+                ..SvgOptions::default()
+            };
+            SvgRenderer::new(w, h, options).render(&state.draw())
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+    print!("{}", text);
+}
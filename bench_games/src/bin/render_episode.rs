@@ -0,0 +1,78 @@
+extern crate toybox;
+extern crate toybox_core;
+
+use std::path::Path;
+use std::process;
+use toybox::graphics::{GifWriter, ImageBuffer, VideoFormat, VideoWriter};
+use toybox::random;
+
+const USAGE: &str = "usage: render_episode GAME OUTPUT.{gif,y4m,rgb,png} [N_STEPS=1000] [SEED=0]
+Plays GAME with uniformly random legal actions and writes every frame to OUTPUT.
+For .png, OUTPUT is a prefix: frames go to OUTPUT_00000.png, OUTPUT_00001.png, ...";
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 3 {
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    }
+    let game = &args[1];
+    let output = Path::new(&args[2]);
+    let n_steps: usize = args.get(3).map_or(1000, |n| n.parse().expect(USAGE));
+    let seed: u32 = args.get(4).map_or(0, |n| n.parse().expect(USAGE));
+
+    let mut sim = toybox::get_simulation_by_name(game).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    sim.reset_seed(seed);
+    let (w, h) = sim.game_size();
+    let actions = sim.legal_action_set();
+    let mut rand = random::Gen::new_from_seed(seed);
+    let mut state = sim.new_game();
+
+    let extension = output.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let mut write_frame: Box<dyn FnMut(usize, &ImageBuffer)> = match extension {
+        "gif" => {
+            // GIF delays are in hundredths of a second, so 60 frames per second becomes 50.
+            let mut gif = GifWriter::create(output, w, h, 20).expect("Could not create GIF");
+            Box::new(move |_, img| gif.write_frame(img).expect("Could not write GIF frame"))
+        }
+        "y4m" | "rgb" => {
+            let format = if extension == "y4m" {
+                VideoFormat::Y4m
+            } else {
+                VideoFormat::RawRgb
+            };
+            let mut video =
+                VideoWriter::create(output, w, h, 60, format).expect("Could not create video");
+            Box::new(move |_, img| video.write_frame(img).expect("Could not write video frame"))
+        }
+        "png" => {
+            let prefix = output.with_extension("");
+            let prefix = prefix
+                .to_str()
+                .expect("Output path should be UTF-8")
+                .to_owned();
+            Box::new(move |i, img| {
+                img.save_png(format!("{}_{:05}.png", prefix, i))
+                    .expect("Could not write PNG")
+            })
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+
+    let mut img = ImageBuffer::alloc(w, h);
+    let mut steps = 0;
+    while steps < n_steps && state.lives() >= 0 {
+        img.render(&state.draw());
+        write_frame(steps, &img);
+        let action = actions[rand._next_u32() as usize % actions.len()];
+        state.update_mut(action.to_input());
+        steps += 1;
+    }
+    println!("{}: score {} after {} steps.", game, state.score(), steps);
+}
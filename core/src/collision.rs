@@ -1,4 +1,6 @@
-use super::graphics::Color;
+use super::graphics::{Color, FixedSpriteData};
+use std::cmp::{max, min};
+use std::f64;
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Rect {
@@ -54,6 +56,271 @@ impl Rect {
     }
 }
 
+/// An axis-aligned box with real-valued coordinates, for objects that move by fractions of a pixel; Rect is its pixel-aligned cousin.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Aabb {
+    /// Left edge.
+    pub x: f64,
+    /// Top edge.
+    pub y: f64,
+    pub w: f64,
+    pub h: f64,
+}
+
+/// Where a sweep first touches its target.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Contact {
+    /// How far along the motion the contact happens, from 0.0 (where it started) to 1.0 (where it would have ended).
+    pub time: f64,
+    /// Unit vector pointing out of the target's surface at the contact, i.e., the axis to reflect velocity over.
+    /// It is (0.0, 0.0) when the two already overlapped at the start.
+    pub normal: (f64, f64),
+}
+
+impl Aabb {
+    pub fn new(x: f64, y: f64, w: f64, h: f64) -> Aabb {
+        Aabb { x, y, w, h }
+    }
+    pub fn from_center(cx: f64, cy: f64, w: f64, h: f64) -> Aabb {
+        Aabb::new(cx - w / 2.0, cy - h / 2.0, w, h)
+    }
+    pub fn x2(&self) -> f64 {
+        self.x + self.w
+    }
+    pub fn y2(&self) -> f64 {
+        self.y + self.h
+    }
+    pub fn center(&self) -> (f64, f64) {
+        (self.x + self.w / 2.0, self.y + self.h / 2.0)
+    }
+    /// Is this point inside the box or on its border?
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x <= self.x2() && y >= self.y && y <= self.y2()
+    }
+    /// Do the two boxes overlap? Boxes that only touch along an edge do not.
+    pub fn intersects(&self, rhs: &Aabb) -> bool {
+        self.x < rhs.x2() && self.x2() > rhs.x && self.y < rhs.y2() && self.y2() > rhs.y
+    }
+    /// The same box, grown by dx on the left and right and by dy on the top and bottom.
+    pub fn expand(&self, dx: f64, dy: f64) -> Aabb {
+        Aabb::new(
+            self.x - dx,
+            self.y - dy,
+            self.w + 2.0 * dx,
+            self.h + 2.0 * dy,
+        )
+    }
+
+    /// Move this box by (dx, dy) and find when it first touches `target`, if it does before the move ends.
+    pub fn sweep(&self, dx: f64, dy: f64, target: &Aabb) -> Option<Contact> {
+        if self.intersects(target) {
+            return Some(Contact {
+                time: 0.0,
+                normal: (0.0, 0.0),
+            });
+        }
+        // Shrink this box to its corner and grow the target to match.
+        let grown = Aabb::new(
+            target.x - self.w,
+            target.y - self.h,
+            target.w + self.w,
+            target.h + self.h,
+        );
+        ray_contact(self.x, self.y, dx, dy, &grown)
+    }
+}
+
+impl<'a> From<&'a Rect> for Aabb {
+    fn from(rect: &'a Rect) -> Aabb {
+        Aabb::new(rect.x.into(), rect.y.into(), rect.w.into(), rect.h.into())
+    }
+}
+
+/// Where a ray from (x, y) along (dx, dy) first enters `target`, between 0 and 1 times (dx, dy); the ray must start outside.
+// RangeInclusive::contains would need Rust 1.35.
+#[allow(clippy::manual_range_contains)]
+fn ray_contact(x: f64, y: f64, dx: f64, dy: f64, target: &Aabb) -> Option<Contact> {
+    let mut enter = f64::NEG_INFINITY;
+    let mut exit = f64::INFINITY;
+    let mut normal = (0.0, 0.0);
+    for &(start, delta, low, high, axis_normal) in &[
+        (x, dx, target.x, target.x2(), (1.0, 0.0)),
+        (y, dy, target.y, target.y2(), (0.0, 1.0)),
+    ] {
+        if delta == 0.0 {
+            // Moving parallel to this axis' edges: it must already be between them.
+            if start <= low || start >= high {
+                return None;
+            }
+            continue;
+        }
+        let t_low = (low - start) / delta;
+        let t_high = (high - start) / delta;
+        let (t_enter, t_exit) = if delta > 0.0 {
+            (t_low, t_high)
+        } else {
+            (t_high, t_low)
+        };
+        if t_enter > enter {
+            enter = t_enter;
+            // The normal faces back against the motion.
+            let sign = -delta.signum();
+            normal = (axis_normal.0 * sign, axis_normal.1 * sign);
+        }
+        exit = exit.min(t_exit);
+    }
+    if enter >= exit || enter < 0.0 || enter > 1.0 {
+        return None;
+    }
+    Some(Contact {
+        time: enter,
+        normal,
+    })
+}
+
+/// A circle, e.g., a ball; see Circle::sweep for bouncing it off boxes.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Circle {
+    pub x: f64,
+    pub y: f64,
+    pub radius: f64,
+}
+
+impl Circle {
+    pub fn new(x: f64, y: f64, radius: f64) -> Circle {
+        Circle { x, y, radius }
+    }
+    /// The point of the box closest to this circle's center (the center itself, if it's inside).
+    fn closest_point(&self, target: &Aabb) -> (f64, f64) {
+        (
+            self.x.max(target.x).min(target.x2()),
+            self.y.max(target.y).min(target.y2()),
+        )
+    }
+    /// Does the circle overlap the box? Touching at a single point does not count.
+    pub fn intersects(&self, target: &Aabb) -> bool {
+        let (px, py) = self.closest_point(target);
+        let (ox, oy) = (self.x - px, self.y - py);
+        ox * ox + oy * oy < self.radius * self.radius
+    }
+
+    /// Move this circle by (dx, dy) and find when it first touches `target`, if it does before the move ends.
+    /// Corners are round, so a glancing hit on one gives a diagonal normal.
+    #[allow(clippy::manual_range_contains)]
+    pub fn sweep(&self, dx: f64, dy: f64, target: &Aabb) -> Option<Contact> {
+        if self.intersects(target) {
+            return Some(Contact {
+                time: 0.0,
+                normal: (0.0, 0.0),
+            });
+        }
+        // The center hits the box grown by the radius, except that the grown box's corners are rounded.
+        let hit = ray_contact(
+            self.x,
+            self.y,
+            dx,
+            dy,
+            &target.expand(self.radius, self.radius),
+        )?;
+        let (hx, hy) = (self.x + hit.time * dx, self.y + hit.time * dy);
+        let beside_x = hx < target.x || hx > target.x2();
+        let beside_y = hy < target.y || hy > target.y2();
+        if !(beside_x && beside_y) {
+            return Some(hit);
+        }
+        // In a corner region: hit the circle of this radius around that corner instead.
+        let cx = if hx < target.x { target.x } else { target.x2() };
+        let cy = if hy < target.y { target.y } else { target.y2() };
+        let (ox, oy) = (self.x - cx, self.y - cy);
+        let a = dx * dx + dy * dy;
+        let b = ox * dx + oy * dy;
+        let c = ox * ox + oy * oy - self.radius * self.radius;
+        let discriminant = b * b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let time = (-b - discriminant.sqrt()) / a;
+        if time < 0.0 || time > 1.0 {
+            return None;
+        }
+        let (nx, ny) = (ox + time * dx, oy + time * dy);
+        let length = (nx * nx + ny * ny).sqrt();
+        Some(Contact {
+            time,
+            normal: (nx / length, ny / length),
+        })
+    }
+}
+
+/// The screen-space overlap of two sprites' bounding boxes, as (x1, y1, x2, y2), if it isn't empty.
+fn overlap_window(
+    a: (i32, i32, i32, i32),
+    b: (i32, i32, i32, i32),
+) -> Option<(i32, i32, i32, i32)> {
+    let x1 = max(a.0, b.0);
+    let y1 = max(a.1, b.1);
+    let x2 = min(a.0 + a.2, b.0 + b.2);
+    let y2 = min(a.1 + a.3, b.1 + b.3);
+    if x1 < x2 && y1 < y2 {
+        Some((x1, y1, x2, y2))
+    } else {
+        None
+    }
+}
+
+/// Pixel-perfect collision: do two sprites, drawn with their top-left corners at these positions, both draw a visible pixel in the same place?
+pub fn sprites_overlap(
+    ax: i32,
+    ay: i32,
+    a: &[Vec<Color>],
+    bx: i32,
+    by: i32,
+    b: &[Vec<Color>],
+) -> bool {
+    let size = |sprite: &[Vec<Color>]| {
+        (
+            sprite.first().map_or(0, |row| row.len()) as i32,
+            sprite.len() as i32,
+        )
+    };
+    let (aw, ah) = size(a);
+    let (bw, bh) = size(b);
+    let (x1, y1, x2, y2) = match overlap_window((ax, ay, aw, ah), (bx, by, bw, bh)) {
+        Some(window) => window,
+        None => return false,
+    };
+    (y1..y2).any(|y| {
+        (x1..x2).any(|x| {
+            a[(y - ay) as usize][(x - ax) as usize].is_visible()
+                && b[(y - by) as usize][(x - bx) as usize].is_visible()
+        })
+    })
+}
+
+/// sprites_overlap for FixedSpriteData, using their cached visibility masks.
+pub fn fixed_sprites_overlap(
+    ax: i32,
+    ay: i32,
+    a: &FixedSpriteData,
+    bx: i32,
+    by: i32,
+    b: &FixedSpriteData,
+) -> bool {
+    let (aw, bw) = (a.width(), b.width());
+    let window = overlap_window((ax, ay, aw, a.height()), (bx, by, bw, b.height()));
+    let (x1, y1, x2, y2) = match window {
+        Some(window) => window,
+        None => return false,
+    };
+    let (a_visible, b_visible) = (a.visible(), b.visible());
+    (y1..y2).any(|y| {
+        (x1..x2).any(|x| {
+            a_visible[((y - ay) * aw + (x - ax)) as usize]
+                && b_visible[((y - by) * bw + (x - bx)) as usize]
+        })
+    })
+}
+
 /// Core algorithm from [developer.mozilla.org](https://developer.mozilla.org/en-US/docs/Games/Techniques/2D_collision_detection)
 fn rect_intersect(r1: &Rect, r2: &Rect) -> bool {
     r1.x <= r2.x + r2.w && r1.x + r1.w >= r2.x && r1.y <= r2.y + r2.h && r1.y + r1.h >= r2.y
@@ -70,4 +337,98 @@ mod tests {
         assert_eq!(false, r1.intersects(&r2));
     }
 
+    #[test]
+    pub fn test_aabb_sweep() {
+        let wall = Aabb::new(10.0, 0.0, 5.0, 20.0);
+        let mover = Aabb::new(0.0, 5.0, 2.0, 2.0);
+
+        // Right edge at 2.0 reaches the wall at 10.0 after 8 of 16 pixels.
+        let contact = mover.sweep(16.0, 0.0, &wall).unwrap();
+        assert_eq!(contact.time, 0.5);
+        assert_eq!(contact.normal, (-1.0, 0.0));
+        // Too short, moving away, or passing above.
+        assert_eq!(mover.sweep(4.0, 0.0, &wall), None);
+        assert_eq!(mover.sweep(-16.0, 0.0, &wall), None);
+        assert_eq!(mover.sweep(16.0, -32.0, &wall), None);
+        // Straight down onto the top.
+        let floor = Aabb::new(-10.0, 10.0, 20.0, 1.0);
+        let contact = mover.sweep(0.0, 6.0, &floor).unwrap();
+        assert_eq!(contact.time, 0.5);
+        assert_eq!(contact.normal, (0.0, -1.0));
+        // Already overlapping.
+        let contact = mover.sweep(1.0, 1.0, &mover.expand(1.0, 1.0)).unwrap();
+        assert_eq!(contact.time, 0.0);
+        assert_eq!(contact.normal, (0.0, 0.0));
+
+        let rect = Aabb::from(&Rect::new(1, 2, 3, 4));
+        assert_eq!(rect, Aabb::from_center(2.5, 4.0, 3.0, 4.0));
+        assert!(rect.contains(4.0, 6.0));
+        assert!(!rect.intersects(&Aabb::new(4.0, 2.0, 1.0, 1.0)));
+    }
+
+    #[test]
+    pub fn test_circle_sweep() {
+        let brick = Aabb::new(10.0, 10.0, 10.0, 10.0);
+        let ball = Circle::new(0.0, 15.0, 2.0);
+        assert!(!ball.intersects(&brick));
+        assert!(Circle::new(9.0, 15.0, 2.0).intersects(&brick));
+        assert!(!Circle::new(8.0, 8.0, 2.0).intersects(&brick));
+
+        // Face hit: the edge of the ball reaches x=10 when its center is at 8.
+        let contact = ball.sweep(16.0, 0.0, &brick).unwrap();
+        assert_eq!(contact.time, 0.5);
+        assert_eq!(contact.normal, (-1.0, 0.0));
+
+        // Aimed straight at the top-left corner: it bounces off diagonally.
+        let ball = Circle::new(0.0, 0.0, 2.0);
+        let contact = ball.sweep(20.0, 20.0, &brick).unwrap();
+        let half = 0.5f64.sqrt();
+        assert!((contact.normal.0 + half).abs() < 1e-9);
+        assert!((contact.normal.1 + half).abs() < 1e-9);
+        assert!((contact.time - (10.0 - 2.0 * half) / 20.0).abs() < 1e-9);
+
+        // Its bounding box would clip the corner, but the ball itself passes by.
+        let ball = Circle::new(0.0, 16.5, 2.0);
+        assert!(Aabb::from_center(0.0, 16.5, 4.0, 4.0)
+            .sweep(20.0, -20.0, &brick)
+            .is_some());
+        assert_eq!(ball.sweep(20.0, -20.0, &brick), None);
+    }
+
+    #[test]
+    pub fn test_sprites_overlap() {
+        let on = Color::rgb(255, 255, 255);
+        let off = Color::invisible();
+        // Two diagonal pixels each, in opposite corners.
+        let a = vec![vec![on, off], vec![off, on]];
+        let b = vec![vec![off, on], vec![on, off]];
+        let (fixed_a, fixed_b) = (
+            FixedSpriteData::new(a.clone()),
+            FixedSpriteData::new(b.clone()),
+        );
+
+        for &(bx, by, expected) in &[
+            (0, 0, false),
+            (1, 0, true),
+            (1, 1, false),
+            (0, 1, true),
+            (-1, 0, true),
+            (2, 0, false),
+        ] {
+            assert_eq!(
+                sprites_overlap(0, 0, &a, bx, by, &b),
+                expected,
+                "{} {}",
+                bx,
+                by
+            );
+            assert_eq!(
+                fixed_sprites_overlap(0, 0, &fixed_a, bx, by, &fixed_b),
+                expected,
+                "{} {}",
+                bx,
+                by
+            );
+        }
+    }
 }
@@ -0,0 +1,115 @@
+use serde_json;
+use std::collections::VecDeque;
+
+/// Something a game reports happening during update_mut, e.g., a brick being hit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    /// A short, game-specific name such as "brick_hit"; payload fields depend on this.
+    pub kind: String,
+    /// Which call to update_mut this happened in, counting from 1 since the state was created or loaded.
+    pub frame: u64,
+    /// Details of the event, e.g., the row and column of the brick.
+    pub payload: serde_json::Value,
+}
+
+/// Games keep one of these in their state and push to it during update_mut; callers drain it through State::drain_events or State::step.
+/// It is not part of a state's JSON, so loading a state starts an empty log at frame 0.
+/// Only the most recent EventLog::CAPACITY events are kept, so a state that is never drained doesn't grow without bound.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EventLog {
+    frame: u64,
+    events: VecDeque<Event>,
+}
+
+impl EventLog {
+    /// How many undrained events are kept; pushing more drops the oldest.
+    pub const CAPACITY: usize = 256;
+
+    /// Call once at the start of each update_mut, so that events are stamped with the right frame.
+    pub fn next_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn push(&mut self, kind: &str, payload: serde_json::Value) {
+        let frame = self.frame;
+        self.push_event(Event {
+            kind: kind.to_owned(),
+            frame,
+            payload,
+        });
+    }
+
+    fn push_event(&mut self, event: Event) {
+        if self.events.len() == EventLog::CAPACITY {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    /// Move every event from a secondary log (e.g., one kept by a game board) into this one, stamped with this log's frame.
+    pub fn append(&mut self, other: &mut EventLog) {
+        let frame = self.frame;
+        for mut event in other.events.drain(..) {
+            event.frame = frame;
+            self.push_event(event);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Remove and return every event logged so far, oldest first.
+    pub fn drain(&mut self) -> Vec<Event> {
+        self.events.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_frames_and_append() {
+        let mut log = EventLog::default();
+        log.next_frame();
+        log.push("a", json!(1));
+        let mut side = EventLog::default();
+        side.push("b", json!({"x": 2}));
+        log.next_frame();
+        log.append(&mut side);
+
+        assert!(side.is_empty());
+        let events = log.drain();
+        assert!(log.is_empty());
+        let summary: Vec<(&str, u64)> = events.iter().map(|e| (e.kind.as_str(), e.frame)).collect();
+        assert_eq!(summary, vec![("a", 1), ("b", 2)]);
+        assert_eq!(events[1].payload["x"], 2);
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut log = EventLog::default();
+        for i in 0..EventLog::CAPACITY + 10 {
+            log.next_frame();
+            log.push("tick", json!(i));
+        }
+        assert_eq!(log.len(), EventLog::CAPACITY);
+        let events = log.drain();
+        // The oldest were dropped.
+        assert_eq!(events[0].frame, 11);
+        assert_eq!(
+            events[EventLog::CAPACITY - 1].frame,
+            EventLog::CAPACITY as u64 + 10
+        );
+    }
+}
@@ -1,13 +1,18 @@
+use gif;
 use png;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
 use std::sync::Arc;
 
-/// For now we only support RGB colors so we don't have to do alpha-blending in our software renderer.
+/// An RGBA color; our software renderers draw translucent colors with source-over alpha blending.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Color {
     pub r: u8,
     pub b: u8,
     pub g: u8,
-    a: u8,
+    /// Opacity: 0 is invisible and 255 covers whatever is underneath.
+    pub a: u8,
 }
 
 impl Color {
@@ -15,9 +20,14 @@ impl Color {
     pub fn rgb(r: u8, g: u8, b: u8) -> Color {
         Color { r, g, b, a: 255 }
     }
-    fn rgba(r: u8, g: u8, b: u8, a: u8) -> Color {
+    /// Create a color from (r, g, b, a) components; see `a`.
+    pub fn rgba(r: u8, g: u8, b: u8, a: u8) -> Color {
         Color { r, g, b, a }
     }
+    /// The same color with a new opacity, e.g., to draw a ghosted copy of a sprite.
+    pub fn with_alpha(self, a: u8) -> Color {
+        Color { a, ..self }
+    }
     pub fn invisible() -> Color {
         Color {
             r: 0,
@@ -29,6 +39,34 @@ impl Color {
     pub fn is_visible(self) -> bool {
         self.a > 0
     }
+    pub fn is_opaque(self) -> bool {
+        self.a == 255
+    }
+    /// Composite this color over `dst` with the source-over operator (straight, not premultiplied, alpha).
+    pub fn blend_over(self, dst: Color) -> Color {
+        if self.is_opaque() || !dst.is_visible() {
+            return self;
+        }
+        if !self.is_visible() {
+            return dst;
+        }
+        let src_a = u32::from(self.a);
+        // How much of dst shows through, scaled by 255.
+        let dst_a = u32::from(dst.a) * (255 - src_a) / 255;
+        let out_a = src_a + dst_a;
+        let mix = |s: u8, d: u8| ((u32::from(s) * src_a + u32::from(d) * dst_a) / out_a) as u8;
+        Color::rgba(
+            mix(self.r, dst.r),
+            mix(self.g, dst.g),
+            mix(self.b, dst.b),
+            out_a as u8,
+        )
+    }
+    /// Composite this color's gray level over a gray level that is already fully opaque.
+    pub fn blend_grayscale_over(self, dst: u8) -> u8 {
+        let src_a = u32::from(self.a);
+        ((u32::from(self.grayscale_byte()) * src_a + u32::from(dst) * (255 - src_a)) / 255) as u8
+    }
     pub fn black() -> Color {
         Color::rgb(0, 0, 0)
     }
@@ -66,17 +104,58 @@ impl<'a> From<&'a (u8, u8, u8)> for Color {
     }
 }
 
-#[derive(PartialEq, Eq, Clone)]
+/// A sprite that never changes once created, so clones can share its pixels and the flattened copies our renderers use.
+#[derive(Clone)]
 pub struct FixedSpriteData {
+    /// Rows of pixels; replace the whole sprite rather than modifying this, or the cached renders will be stale.
     pub data: Arc<Vec<Vec<Color>>>,
-    // TODO: cache grayscale and rgba renders as Arc<Option<Vec<u8>>>?
+    cache: Arc<SpriteCache>,
 }
+
+/// Renders of a FixedSpriteData, computed once when it is created.
+struct SpriteCache {
+    rgba: Vec<u8>,
+    grayscale: Vec<u8>,
+    visible: Vec<bool>,
+}
+
+impl PartialEq for FixedSpriteData {
+    fn eq(&self, other: &FixedSpriteData) -> bool {
+        self.data == other.data
+    }
+}
+impl Eq for FixedSpriteData {}
+
 impl FixedSpriteData {
     pub fn new(data: Vec<Vec<Color>>) -> FixedSpriteData {
+        let cache = {
+            let pixels = || data.iter().flat_map(|row| row.iter());
+            SpriteCache {
+                rgba: pixels().flat_map(|c| vec![c.r, c.g, c.b, c.a]).collect(),
+                grayscale: pixels().map(|c| c.grayscale_byte()).collect(),
+                visible: pixels().map(|c| c.is_visible()).collect(),
+            }
+        };
         FixedSpriteData {
             data: Arc::new(data),
+            cache: Arc::new(cache),
         }
     }
+
+    /// Row-major RGBA bytes, 4 per pixel, as ImageBuffer stores them.
+    pub fn rgba(&self) -> &[u8] {
+        &self.cache.rgba
+    }
+
+    /// Row-major grayscale bytes, one per pixel, as GrayscaleBuffer draws opaque pixels.
+    pub fn grayscale(&self) -> &[u8] {
+        &self.cache.grayscale
+    }
+
+    /// Row-major visibility mask: true wherever the sprite draws anything.
+    pub fn visible(&self) -> &[bool] {
+        &self.cache.visible
+    }
     pub fn width(&self) -> i32 {
         self.data[0].len() as i32
     }
@@ -123,6 +202,11 @@ impl FixedSpriteData {
         FixedSpriteData::new(output)
     }
 
+    /// Every distinct opaque color in this sprite, e.g., for Simulation::palette.
+    pub fn colors(&self) -> Vec<Color> {
+        unique_colors(self.data.iter().flat_map(|row| row.iter().cloned()))
+    }
+
     pub fn find_visible_color(&self) -> Option<Color> {
         for row in self.data.iter() {
             for px in row {
@@ -177,6 +261,25 @@ impl SpriteData {
     }
 }
 
+/// Says which object a Drawable belongs to, so that a MaskBuffer can render segmentation masks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tag {
+    /// 1 + the index of the object's kind in Simulation::object_kinds; 0 is background.
+    pub class_id: u8,
+    /// Tells apart objects of the same kind, e.g., 1 + a brick's index; 0 means no particular instance.
+    pub instance_id: i32,
+}
+
+impl Tag {
+    /// The tag of anything not inside a Drawable::Tagged.
+    pub fn background() -> Tag {
+        Tag {
+            class_id: 0,
+            instance_id: 0,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub enum Drawable {
     Clear(Color),
@@ -195,6 +298,8 @@ pub enum Drawable {
         y: i32,
         data: FixedSpriteData,
     },
+    /// Any other command, marked as part of an object for MaskBuffer; other buffers draw it as usual.
+    Tagged(Tag, Box<Drawable>),
 }
 
 impl Drawable {
@@ -204,6 +309,125 @@ impl Drawable {
     pub fn sprite(x: i32, y: i32, sprite: FixedSpriteData) -> Drawable {
         Drawable::StaticSprite { x, y, data: sprite }
     }
+    /// Mark this command as drawing (part of) an object; see Tag.
+    pub fn tagged(self, class_id: u8, instance_id: i32) -> Drawable {
+        Drawable::Tagged(
+            Tag {
+                class_id,
+                instance_id,
+            },
+            Box::new(self),
+        )
+    }
+}
+
+/// Something Drawables can be rasterized onto: an RGBA image, a grayscale image, palette indices, a segmentation mask...
+///
+/// Implementations only say how to clear and how to write one pixel; the provided methods turn rectangles and sprites into pixels, so every output format draws the same shapes.
+pub trait Canvas {
+    /// Width and height in pixels.
+    fn size(&self) -> (i32, i32);
+    /// Fill everything, for Drawable::Clear.
+    fn clear(&mut self, color: Color, tag: Tag);
+    /// Draw one visible (but maybe translucent) pixel; (x, y) is always on the canvas.
+    fn put_pixel(&mut self, x: i32, y: i32, color: Color, tag: Tag);
+
+    /// Draw a visible color on row y from x0 up to (but not including) x1; the span is always on the canvas.
+    /// Override this to fill whole slices at once; the default draws pixel by pixel.
+    fn fill_span(&mut self, y: i32, x0: i32, x1: i32, color: Color, tag: Tag) {
+        for x in x0..x1 {
+            self.put_pixel(x, y, color, tag)
+        }
+    }
+
+    /// Draw a row of sprite pixels starting at (x, y), skipping invisible ones; the row is always on the canvas.
+    /// Override this to write whole slices at once; the default draws pixel by pixel.
+    fn draw_span(&mut self, x: i32, y: i32, colors: &[Color], tag: Tag) {
+        for (i, &color) in colors.iter().enumerate() {
+            if color.is_visible() {
+                self.put_pixel(x + i as i32, y, color, tag)
+            }
+        }
+    }
+
+    /// Draw the on-canvas part of a rectangle.
+    fn fill_rect(&mut self, color: Color, x: i32, y: i32, w: i32, h: i32, tag: Tag) {
+        let (width, height) = self.size();
+        let (x0, x1) = (x.max(0), (x + w).min(width));
+        if !color.is_visible() || x0 >= x1 {
+            return;
+        }
+        for yi in y.max(0)..(y + h).min(height) {
+            self.fill_span(yi, x0, x1, color, tag)
+        }
+    }
+
+    /// Draw the visible, on-canvas pixels of a sprite whose top-left corner is (x, y).
+    fn draw_sprite(&mut self, x: i32, y: i32, data: &[Vec<Color>], tag: Tag) {
+        let (width, height) = self.size();
+        let (first_row, last_row) = clip(y, data.len() as i32, height);
+        for (yi, row) in data.iter().enumerate().take(last_row).skip(first_row) {
+            let (start, end) = clip(x, row.len() as i32, width);
+            if start < end {
+                self.draw_span(x + start as i32, y + yi as i32, &row[start..end], tag)
+            }
+        }
+    }
+
+    /// Draw a FixedSpriteData; override this to use its cached renders, as the default only uses its pixels.
+    fn draw_fixed_sprite(&mut self, x: i32, y: i32, sprite: &FixedSpriteData, tag: Tag) {
+        self.draw_sprite(x, y, &sprite.data, tag)
+    }
+
+    /// Draw one command; `tag` is the innermost Drawable::Tagged around it.
+    fn draw(&mut self, cmd: &Drawable, tag: Tag) {
+        match cmd {
+            &Drawable::Clear(color) => self.clear(color, tag),
+            &Drawable::Rectangle { color, x, y, w, h } => self.fill_rect(color, x, y, w, h, tag),
+            &Drawable::StaticSprite {
+                x,
+                y,
+                data: ref sprite,
+            } => self.draw_fixed_sprite(x, y, sprite, tag),
+            &Drawable::DestructibleSprite(ref sprite) => {
+                self.draw_sprite(sprite.x, sprite.y, &sprite.data, tag)
+            }
+            &Drawable::Tagged(inner_tag, ref inner) => self.draw(inner, inner_tag),
+        }
+    }
+
+    /// Draw a frame's commands in order.
+    fn render(&mut self, commands: &[Drawable]) {
+        for cmd in commands {
+            self.draw(cmd, Tag::background());
+        }
+    }
+}
+
+/// The part of [pos, pos + len) that lies within [0, limit), as a range of offsets from pos.
+fn clip(pos: i32, len: i32, limit: i32) -> (usize, usize) {
+    let start = (-pos).max(0).min(len);
+    let end = (limit - pos).min(len).max(start);
+    (start as usize, end as usize)
+}
+
+/// Calls draw_row(canvas_offset, sprite_offset, len) for each on-canvas row of a w by h sprite at (x, y), with offsets into row-major buffers.
+fn for_each_sprite_row<F: FnMut(usize, usize, usize)>(
+    (width, height): (i32, i32),
+    x: i32,
+    y: i32,
+    (w, h): (i32, i32),
+    mut draw_row: F,
+) {
+    let (first_row, last_row) = clip(y, h, height);
+    let (start, end) = clip(x, w, width);
+    if start >= end {
+        return;
+    }
+    for yi in first_row..last_row {
+        let canvas_offset = (y + yi as i32) as usize * width as usize + (x + start as i32) as usize;
+        draw_row(canvas_offset, yi * w as usize + start, end - start);
+    }
 }
 
 pub struct GrayscaleBuffer {
@@ -219,70 +443,109 @@ impl GrayscaleBuffer {
             data: vec![0; (width * height) as usize],
         }
     }
-    #[inline(always)]
-    fn set_pixel(&mut self, x: i32, y: i32, color: u8) {
-        let start = (y * self.width) + x;
-        if start < 0 {
-            return;
+    pub fn render(&mut self, commands: &[Drawable]) {
+        Canvas::render(self, commands)
+    }
+
+    /// Encode this frame as an 8-bit grayscale PNG.
+    pub fn write_png<W: Write>(&self, w: W) -> io::Result<()> {
+        write_png(
+            w,
+            self.width,
+            self.height,
+            png::ColorType::Grayscale,
+            &self.data,
+        )
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_png(BufWriter::new(File::create(path)?))
+    }
+
+    /// A copy of part of this frame at a new size, e.g., an 84x84 observation for DQN.
+    pub fn resized(
+        &self,
+        crop: Crop,
+        width: i32,
+        height: i32,
+        method: Resample,
+    ) -> GrayscaleBuffer {
+        GrayscaleBuffer {
+            width,
+            height,
+            data: resample(
+                &self.data,
+                (self.width, self.height),
+                1,
+                crop,
+                (width, height),
+                method,
+            ),
         }
-        let start = start as usize;
-        if start >= self.data.len() {
-            return;
+    }
+}
+
+impl Canvas for GrayscaleBuffer {
+    fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+    fn clear(&mut self, color: Color, _tag: Tag) {
+        let fill = color.grayscale_byte();
+        for x in self.data.iter_mut() {
+            *x = fill;
         }
-        self.data[start] = color;
     }
     #[inline(always)]
-    fn set_pixel_alpha(&mut self, x: i32, y: i32, color: Color) {
-        if color.is_visible() {
-            self.set_pixel(x, y, color.grayscale_byte())
+    fn put_pixel(&mut self, x: i32, y: i32, color: Color, _tag: Tag) {
+        let index = (y * self.width + x) as usize;
+        self.data[index] = if color.is_opaque() {
+            color.grayscale_byte()
+        } else {
+            color.blend_grayscale_over(self.data[index])
+        };
+    }
+    fn fill_span(&mut self, y: i32, x0: i32, x1: i32, color: Color, _tag: Tag) {
+        let row = (y * self.width) as usize;
+        let span = &mut self.data[row + x0 as usize..row + x1 as usize];
+        if color.is_opaque() {
+            let fill = color.grayscale_byte();
+            for px in span.iter_mut() {
+                *px = fill;
+            }
+        } else {
+            for px in span.iter_mut() {
+                *px = color.blend_grayscale_over(*px);
+            }
         }
     }
-
-    pub fn render(&mut self, commands: &[Drawable]) {
-        for cmd in commands {
-            match cmd {
-                &Drawable::Clear(color) => {
-                    let fill = color.grayscale_byte();
-                    for x in self.data.iter_mut() {
-                        *x = fill;
-                    }
-                }
-                &Drawable::Rectangle { color, x, y, w, h } => {
-                    let fill = color.grayscale_byte();
-                    for yi in y..(y + h) {
-                        for xi in x..(x + w) {
-                            self.set_pixel(xi, yi, fill)
-                        }
-                    }
-                }
-                &Drawable::DestructibleSprite(ref sprite) => {
-                    let w = sprite.width();
-                    let h = sprite.height();
-                    let (x, y) = sprite.position();
-                    for yi in 0..h {
-                        for xi in 0..w {
-                            let color = sprite.data[yi as usize][xi as usize];
-                            self.set_pixel_alpha(xi + x, yi + y, color)
-                        }
-                    }
-                }
-                &Drawable::StaticSprite {
-                    x,
-                    y,
-                    data: ref sprite,
-                } => {
-                    let w = sprite.width();
-                    let h = sprite.height();
-                    for yi in 0..h {
-                        for xi in 0..w {
-                            let color = sprite.data[yi as usize][xi as usize];
-                            self.set_pixel_alpha(xi + x, yi + y, color)
-                        }
+    fn draw_span(&mut self, x: i32, y: i32, colors: &[Color], _tag: Tag) {
+        let start = (y * self.width + x) as usize;
+        let span = &mut self.data[start..start + colors.len()];
+        for (px, &color) in span.iter_mut().zip(colors) {
+            if color.is_opaque() {
+                *px = color.grayscale_byte();
+            } else if color.is_visible() {
+                *px = color.blend_grayscale_over(*px);
+            }
+        }
+    }
+    fn draw_fixed_sprite(&mut self, x: i32, y: i32, sprite: &FixedSpriteData, _tag: Tag) {
+        let (gray, rgba) = (sprite.grayscale(), sprite.rgba());
+        let size = (sprite.width(), sprite.height());
+        let data = &mut self.data;
+        for_each_sprite_row((self.width, self.height), x, y, size, |dst, src, len| {
+            for i in 0..len {
+                let (px, k) = (&mut data[dst + i], src + i);
+                match rgba[4 * k + 3] {
+                    0 => {}
+                    255 => *px = gray[k],
+                    a => {
+                        let color = Color::rgba(rgba[4 * k], rgba[4 * k + 1], rgba[4 * k + 2], a);
+                        *px = color.blend_grayscale_over(*px);
                     }
                 }
             }
-        }
-        // Done.
+        });
     }
 }
 
@@ -301,94 +564,949 @@ impl ImageBuffer {
         }
     }
 
+    /// The color at (x, y), or None if that is off the image.
+    pub fn get_pixel(&self, x: i32, y: i32) -> Option<Color> {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return None;
+        }
+        let start = ((y * self.width + x) * 4) as usize;
+        let px = &self.data[start..start + 4];
+        Some(Color::rgba(px[0], px[1], px[2], px[3]))
+    }
+
+    /// Used in breakout_wp
+    pub fn render_sprite(&mut self, data: &Vec<Vec<Color>>) {
+        self.draw_sprite(0, 0, data, Tag::background())
+    }
+
+    pub fn render(&mut self, commands: &[Drawable]) {
+        Canvas::render(self, commands)
+    }
+
+    /// Encode this frame as an 8-bit RGBA PNG.
+    pub fn write_png<W: Write>(&self, w: W) -> io::Result<()> {
+        write_png(w, self.width, self.height, png::ColorType::RGBA, &self.data)
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_png(BufWriter::new(File::create(path)?))
+    }
+
+    /// A copy of part of this frame at a new size; see GrayscaleBuffer::resized.
+    pub fn resized(&self, crop: Crop, width: i32, height: i32, method: Resample) -> ImageBuffer {
+        ImageBuffer {
+            width,
+            height,
+            data: resample(
+                &self.data,
+                (self.width, self.height),
+                4,
+                crop,
+                (width, height),
+                method,
+            ),
+        }
+    }
+}
+
+impl Canvas for ImageBuffer {
+    fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+    fn clear(&mut self, color: Color, _tag: Tag) {
+        fill_repeated(&mut self.data, &[color.r, color.g, color.b, color.a]);
+    }
     #[inline(always)]
-    fn set_pixel(&mut self, x: i32, y: i32, color: Color) {
-        debug_assert!(color.is_visible());
-        let start = (y * self.width * 4) + (x * 4);
-        if start < 0 {
-            return;
+    fn put_pixel(&mut self, x: i32, y: i32, color: Color, _tag: Tag) {
+        let start = ((y * self.width + x) * 4) as usize;
+        let px = &mut self.data[start..start + 4];
+        let color = if color.is_opaque() {
+            color
+        } else {
+            color.blend_over(Color::rgba(px[0], px[1], px[2], px[3]))
+        };
+        px[0] = color.r;
+        px[1] = color.g;
+        px[2] = color.b;
+        px[3] = color.a;
+    }
+    fn fill_span(&mut self, y: i32, x0: i32, x1: i32, color: Color, _tag: Tag) {
+        let row = (y * self.width * 4) as usize;
+        let span = &mut self.data[row + 4 * x0 as usize..row + 4 * x1 as usize];
+        if color.is_opaque() {
+            fill_repeated(span, &[color.r, color.g, color.b, color.a]);
+        } else {
+            for px in span.chunks_exact_mut(4) {
+                let c = color.blend_over(Color::rgba(px[0], px[1], px[2], px[3]));
+                px.copy_from_slice(&[c.r, c.g, c.b, c.a]);
+            }
         }
-        let start = start as usize;
-        if start >= self.data.len() {
-            return;
+    }
+    fn draw_span(&mut self, x: i32, y: i32, colors: &[Color], _tag: Tag) {
+        let start = ((y * self.width + x) * 4) as usize;
+        let span = &mut self.data[start..start + 4 * colors.len()];
+        for (px, &color) in span.chunks_exact_mut(4).zip(colors) {
+            if color.is_opaque() {
+                px.copy_from_slice(&[color.r, color.g, color.b, color.a]);
+            } else if color.is_visible() {
+                let c = color.blend_over(Color::rgba(px[0], px[1], px[2], px[3]));
+                px.copy_from_slice(&[c.r, c.g, c.b, c.a]);
+            }
         }
-        self.data[start] = color.r;
-        self.data[start + 1] = color.g;
-        self.data[start + 2] = color.b;
-        self.data[start + 3] = color.a;
     }
+    fn draw_fixed_sprite(&mut self, x: i32, y: i32, sprite: &FixedSpriteData, _tag: Tag) {
+        let rgba = sprite.rgba();
+        let size = (sprite.width(), sprite.height());
+        let data = &mut self.data;
+        for_each_sprite_row((self.width, self.height), x, y, size, |dst, src, len| {
+            let dst = &mut data[4 * dst..4 * (dst + len)];
+            let src = &rgba[4 * src..4 * (src + len)];
+            for (px, color) in dst.chunks_exact_mut(4).zip(src.chunks_exact(4)) {
+                match color[3] {
+                    0 => {}
+                    255 => px.copy_from_slice(color),
+                    a => {
+                        let color = Color::rgba(color[0], color[1], color[2], a);
+                        let c = color.blend_over(Color::rgba(px[0], px[1], px[2], px[3]));
+                        px.copy_from_slice(&[c.r, c.g, c.b, c.a]);
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Fill `data` with copies of `pattern` (whose length divides the length of `data`), doubling the filled prefix each time so that the copies are large memcpys.
+fn fill_repeated(data: &mut [u8], pattern: &[u8]) {
+    if data.is_empty() {
+        return;
+    }
+    data[..pattern.len()].copy_from_slice(pattern);
+    let mut filled = pattern.len();
+    while filled < data.len() {
+        let n = filled.min(data.len() - filled);
+        let (done, rest) = data.split_at_mut(filled);
+        rest[..n].copy_from_slice(&done[..n]);
+        filled += n;
+    }
+}
 
+/// Renders which object covers each pixel instead of its color: a class id and an instance id per pixel, taken from Drawable::Tagged.
+/// Untagged commands cover whatever was under them with background (zeros).
+pub struct MaskBuffer {
+    pub width: i32,
+    pub height: i32,
+    /// One Tag::class_id per pixel.
+    pub class_ids: Vec<u8>,
+    /// One Tag::instance_id per pixel.
+    pub instance_ids: Vec<i32>,
+}
+impl MaskBuffer {
+    pub fn alloc(width: i32, height: i32) -> MaskBuffer {
+        MaskBuffer {
+            width,
+            height,
+            class_ids: vec![0; (width * height) as usize],
+            instance_ids: vec![0; (width * height) as usize],
+        }
+    }
+
+    pub fn render(&mut self, commands: &[Drawable]) {
+        Canvas::render(self, commands)
+    }
+}
+
+impl Canvas for MaskBuffer {
+    fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+    fn clear(&mut self, _color: Color, tag: Tag) {
+        for class_id in self.class_ids.iter_mut() {
+            *class_id = tag.class_id;
+        }
+        for instance_id in self.instance_ids.iter_mut() {
+            *instance_id = tag.instance_id;
+        }
+    }
     #[inline(always)]
-    fn set_pixel_alpha(&mut self, x: i32, y: i32, color: Color) {
-        if color.is_visible() {
-            self.set_pixel(x, y, color)
+    fn put_pixel(&mut self, x: i32, y: i32, _color: Color, tag: Tag) {
+        let index = (y * self.width + x) as usize;
+        self.class_ids[index] = tag.class_id;
+        self.instance_ids[index] = tag.instance_id;
+    }
+    fn draw_fixed_sprite(&mut self, x: i32, y: i32, sprite: &FixedSpriteData, tag: Tag) {
+        let visible = sprite.visible();
+        let size = (sprite.width(), sprite.height());
+        let (class_ids, instance_ids) = (&mut self.class_ids, &mut self.instance_ids);
+        for_each_sprite_row((self.width, self.height), x, y, size, |dst, src, len| {
+            for i in 0..len {
+                if visible[src + i] {
+                    class_ids[dst + i] = tag.class_id;
+                    instance_ids[dst + i] = tag.instance_id;
+                }
+            }
+        });
+    }
+    fn fill_span(&mut self, y: i32, x0: i32, x1: i32, _color: Color, tag: Tag) {
+        let row = (y * self.width) as usize;
+        let (start, end) = (row + x0 as usize, row + x1 as usize);
+        for class_id in self.class_ids[start..end].iter_mut() {
+            *class_id = tag.class_id;
+        }
+        for instance_id in self.instance_ids[start..end].iter_mut() {
+            *instance_id = tag.instance_id;
         }
     }
+}
 
-    /// Used in breakout_wp
-    pub fn render_sprite(&mut self, data: &Vec<Vec<Color>>) {
-        let h = data.len() as i32;
-        let w = data[0].len() as i32;
-        for yi in 0..h {
-            for xi in 0..w {
-                let color = data[yi as usize][xi as usize];
-                self.set_pixel_alpha(xi, yi, color)
+/// One byte per pixel: the index of its color in `palette`, a compact and lossless observation for games with few colors.
+/// Opaque colors not yet in the palette are appended as they are drawn, until it holds 256; after that they map to the closest entry.
+/// Translucent pixels are blended over the color underneath and then mapped to the closest entry, so anti-aliased sprite edges do not grow the palette.
+pub struct PaletteBuffer {
+    pub width: i32,
+    pub height: i32,
+    /// Opaque colors.
+    pub palette: Vec<Color>,
+    pub data: Vec<u8>,
+}
+impl PaletteBuffer {
+    /// A buffer that starts out as palette entry 0, which is black if `palette` is empty.
+    pub fn alloc(width: i32, height: i32, palette: Vec<Color>) -> PaletteBuffer {
+        let mut palette = palette;
+        if palette.is_empty() {
+            palette.push(Color::black());
+        }
+        PaletteBuffer {
+            width,
+            height,
+            palette,
+            data: vec![0; (width * height) as usize],
+        }
+    }
+
+    /// The index of `color`, ignoring alpha, adding it to the palette if there is room.
+    pub fn index_of(&mut self, color: Color) -> u8 {
+        let color = color.with_alpha(255);
+        if let Some(index) = self.palette.iter().position(|&c| c == color) {
+            return index as u8;
+        }
+        if self.palette.len() < 256 {
+            self.palette.push(color);
+            return (self.palette.len() - 1) as u8;
+        }
+        self.closest_index(color)
+    }
+
+    /// The index of the palette entry nearest to `color` in RGB space.
+    pub fn closest_index(&self, color: Color) -> u8 {
+        let distance = |c: &Color| {
+            let dr = i32::from(c.r) - i32::from(color.r);
+            let dg = i32::from(c.g) - i32::from(color.g);
+            let db = i32::from(c.b) - i32::from(color.b);
+            dr * dr + dg * dg + db * db
+        };
+        (0..self.palette.len())
+            .min_by_key(|&i| distance(&self.palette[i]))
+            .unwrap_or(0) as u8
+    }
+
+    pub fn render(&mut self, commands: &[Drawable]) {
+        Canvas::render(self, commands)
+    }
+}
+
+impl Canvas for PaletteBuffer {
+    fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+    fn clear(&mut self, color: Color, _tag: Tag) {
+        let fill = self.index_of(color);
+        for x in self.data.iter_mut() {
+            *x = fill;
+        }
+    }
+    #[inline(always)]
+    fn put_pixel(&mut self, x: i32, y: i32, color: Color, _tag: Tag) {
+        let index = (y * self.width + x) as usize;
+        self.data[index] = if color.is_opaque() {
+            self.index_of(color)
+        } else {
+            let under = self.palette[self.data[index] as usize];
+            self.closest_index(color.blend_over(under))
+        };
+    }
+    fn fill_span(&mut self, y: i32, x0: i32, x1: i32, color: Color, tag: Tag) {
+        if !color.is_opaque() {
+            for x in x0..x1 {
+                self.put_pixel(x, y, color, tag)
             }
+            return;
+        }
+        let fill = self.index_of(color);
+        let row = (y * self.width) as usize;
+        for px in self.data[row + x0 as usize..row + x1 as usize].iter_mut() {
+            *px = fill;
+        }
+    }
+}
+
+/// The opaque colors, without duplicates, in the order first seen; a palette for PaletteBuffer.
+/// Translucent colors are left out, since PaletteBuffer maps them to the closest entry anyway.
+pub fn unique_colors<I: IntoIterator<Item = Color>>(colors: I) -> Vec<Color> {
+    let mut output: Vec<Color> = Vec::new();
+    for color in colors.into_iter().filter(|c| c.is_opaque()) {
+        if !output.contains(&color) {
+            output.push(color);
         }
     }
+    output
+}
 
-    fn render_rectangle(&mut self, color: Color, x: i32, y: i32, w: i32, h: i32) {
-        for yi in y..(y + h) {
-            for xi in x..(x + w) {
-                self.set_pixel(xi, yi, color)
+/// How to compute each output pixel when changing the size of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Resample {
+    /// Copy the closest source pixel; fast, and keeps colors exact.
+    Nearest,
+    /// Interpolate between the four closest source pixels.
+    Bilinear,
+    /// Average every source pixel the output pixel covers, weighted by overlap; best for shrinking, like OpenCV's INTER_AREA.
+    Area,
+}
+
+impl Resample {
+    /// A stable integer for each method, for use across the C API.
+    pub fn from_int(x: i32) -> Option<Resample> {
+        match x {
+            0 => Some(Resample::Nearest),
+            1 => Some(Resample::Bilinear),
+            2 => Some(Resample::Area),
+            _ => None,
+        }
+    }
+}
+
+/// A rectangular region of a frame, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Crop {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+impl Crop {
+    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Crop {
+        Crop { x, y, w, h }
+    }
+    /// The whole of a width by height frame.
+    pub fn full(width: i32, height: i32) -> Crop {
+        Crop::new(0, 0, width, height)
+    }
+    /// The part of this region that lies inside a width by height frame.
+    fn clamp(&self, width: i32, height: i32) -> Crop {
+        let x1 = self.x.max(0).min(width);
+        let y1 = self.y.max(0).min(height);
+        let x2 = (self.x + self.w).max(x1).min(width);
+        let y2 = (self.y + self.h).max(y1).min(height);
+        Crop::new(x1, y1, x2 - x1, y2 - y1)
+    }
+}
+
+/// For each output coordinate along one axis, the source pixels it covers and how much of each, for Resample::Area.
+fn area_weights(src_len: usize, out_len: usize) -> Vec<Vec<(usize, f64)>> {
+    let scale = src_len as f64 / out_len as f64;
+    (0..out_len)
+        .map(|o| {
+            let start = o as f64 * scale;
+            let end = start + scale;
+            let mut weights = Vec::new();
+            let mut i = start.floor() as usize;
+            while (i as f64) < end && i < src_len {
+                let overlap = end.min(i as f64 + 1.0) - start.max(i as f64);
+                if overlap > 0.0 {
+                    weights.push((i, overlap / scale));
+                }
+                i += 1;
+            }
+            weights
+        })
+        .collect()
+}
+
+/// Crop and resize a row-major image with `channels` bytes per pixel (e.g., 1 for GrayscaleBuffer, 4 for ImageBuffer).
+/// The crop is clamped to the image; an empty crop gives an all-zero output.
+pub fn resample(
+    data: &[u8],
+    (width, height): (i32, i32),
+    channels: usize,
+    crop: Crop,
+    (out_width, out_height): (i32, i32),
+    method: Resample,
+) -> Vec<u8> {
+    assert_eq!(data.len(), (width * height) as usize * channels);
+    let crop = crop.clamp(width, height);
+    let (out_w, out_h) = (out_width.max(0) as usize, out_height.max(0) as usize);
+    let mut output = vec![0; out_w * out_h * channels];
+    if crop.w == 0 || crop.h == 0 {
+        return output;
+    }
+    let (cw, ch) = (crop.w as usize, crop.h as usize);
+    let src = |x: usize, y: usize, c: usize| -> f64 {
+        let index = ((crop.y as usize + y) * width as usize + crop.x as usize + x) * channels + c;
+        f64::from(data[index])
+    };
+    let x_scale = cw as f64 / out_w.max(1) as f64;
+    let y_scale = ch as f64 / out_h.max(1) as f64;
+    let (x_area, y_area) = match method {
+        Resample::Area => (area_weights(cw, out_w), area_weights(ch, out_h)),
+        _ => (Vec::new(), Vec::new()),
+    };
+
+    for oy in 0..out_h {
+        for ox in 0..out_w {
+            for c in 0..channels {
+                let value = match method {
+                    Resample::Nearest => {
+                        let sx = ((ox as f64 + 0.5) * x_scale) as usize;
+                        let sy = ((oy as f64 + 0.5) * y_scale) as usize;
+                        src(sx.min(cw - 1), sy.min(ch - 1), c)
+                    }
+                    Resample::Bilinear => {
+                        // Pixel centers line up, as in OpenCV's INTER_LINEAR.
+                        let fx = ((ox as f64 + 0.5) * x_scale - 0.5).max(0.0);
+                        let fy = ((oy as f64 + 0.5) * y_scale - 0.5).max(0.0);
+                        let (x0, y0) = ((fx as usize).min(cw - 1), (fy as usize).min(ch - 1));
+                        let (x1, y1) = ((x0 + 1).min(cw - 1), (y0 + 1).min(ch - 1));
+                        let (tx, ty) = (fx - x0 as f64, fy - y0 as f64);
+                        let top = src(x0, y0, c) * (1.0 - tx) + src(x1, y0, c) * tx;
+                        let bottom = src(x0, y1, c) * (1.0 - tx) + src(x1, y1, c) * tx;
+                        top * (1.0 - ty) + bottom * ty
+                    }
+                    Resample::Area => {
+                        let mut total = 0.0;
+                        for &(sy, wy) in &y_area[oy] {
+                            for &(sx, wx) in &x_area[ox] {
+                                total += src(sx, sy, c) * wx * wy;
+                            }
+                        }
+                        total
+                    }
+                };
+                output[(oy * out_w + ox) * channels + c] = value.round().max(0.0).min(255.0) as u8;
+            }
+        }
+    }
+    output
+}
+
+fn write_png<W: Write>(
+    w: W,
+    width: i32,
+    height: i32,
+    color_type: png::ColorType,
+    data: &[u8],
+) -> io::Result<()> {
+    use png::HasParameters;
+    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
+    encoder.set(color_type).set(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(data)?;
+    Ok(())
+}
+
+/// Writes ImageBuffer frames as a looping animated GIF, e.g., to look at an episode.
+pub struct GifWriter<W: Write> {
+    encoder: gif::Encoder<W>,
+    width: i32,
+    height: i32,
+    /// GIFs count time in hundredths of a second.
+    delay_centis: u16,
+}
+
+impl<W: Write> GifWriter<W> {
+    /// Frames must all be width by height, which GIF limits to 65535; each is shown for frame_delay_ms.
+    pub fn new(w: W, width: i32, height: i32, frame_delay_ms: u32) -> io::Result<GifWriter<W>> {
+        if width <= 0 || height <= 0 || width > 0xffff || height > 0xffff {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("GIF cannot be {}x{}", width, height),
+            ));
+        }
+        use gif::SetParameter;
+        let mut encoder = gif::Encoder::new(w, width as u16, height as u16, &[])?;
+        encoder.set(gif::Repeat::Infinite)?;
+        Ok(GifWriter {
+            encoder,
+            width,
+            height,
+            delay_centis: (frame_delay_ms / 10).min(0xffff) as u16,
+        })
+    }
+
+    pub fn write_frame(&mut self, frame: &ImageBuffer) -> io::Result<()> {
+        check_frame_size(frame, self.width, self.height)?;
+        let mut pixels = frame.data.clone();
+        let mut gif_frame =
+            gif::Frame::from_rgba_speed(self.width as u16, self.height as u16, &mut pixels, 10);
+        gif_frame.delay = self.delay_centis;
+        self.encoder.write_frame(&gif_frame)
+    }
+}
+
+impl GifWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        width: i32,
+        height: i32,
+        frame_delay_ms: u32,
+    ) -> io::Result<GifWriter<BufWriter<File>>> {
+        GifWriter::new(
+            BufWriter::new(File::create(path)?),
+            width,
+            height,
+            frame_delay_ms,
+        )
+    }
+}
+
+/// The byte layouts VideoWriter can produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VideoFormat {
+    /// Headerless RGB24, width * height * 3 bytes per frame; e.g., `ffmpeg -f rawvideo -pix_fmt rgb24 -s WxH -i -`.
+    RawRgb,
+    /// YUV4MPEG2 with full-resolution (4:4:4) BT.601 chroma, which ffmpeg and mpv read directly.
+    Y4m,
+}
+
+/// Streams ImageBuffer frames as uncompressed video; alpha is dropped.
+pub struct VideoWriter<W: Write> {
+    w: W,
+    width: i32,
+    height: i32,
+    format: VideoFormat,
+}
+
+impl<W: Write> VideoWriter<W> {
+    /// Frames must all be width by height; fps only matters to the Y4M header.
+    pub fn new(
+        mut w: W,
+        width: i32,
+        height: i32,
+        fps: u32,
+        format: VideoFormat,
+    ) -> io::Result<VideoWriter<W>> {
+        if format == VideoFormat::Y4m {
+            writeln!(
+                w,
+                "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                width, height, fps
+            )?;
+        }
+        Ok(VideoWriter {
+            w,
+            width,
+            height,
+            format,
+        })
+    }
+
+    pub fn write_frame(&mut self, frame: &ImageBuffer) -> io::Result<()> {
+        check_frame_size(frame, self.width, self.height)?;
+        match self.format {
+            VideoFormat::RawRgb => {
+                let mut rgb = Vec::with_capacity(frame.data.len() / 4 * 3);
+                for px in frame.data.chunks(4) {
+                    rgb.extend_from_slice(&px[0..3]);
+                }
+                self.w.write_all(&rgb)
+            }
+            VideoFormat::Y4m => {
+                let plane = frame.data.len() / 4;
+                let mut yuv = vec![0; plane * 3];
+                for (i, px) in frame.data.chunks(4).enumerate() {
+                    let (r, g, b) = (f64::from(px[0]), f64::from(px[1]), f64::from(px[2]));
+                    // BT.601, limited range.
+                    yuv[i] = (16.0 + (65.738 * r + 129.057 * g + 25.064 * b) / 256.0).round() as u8;
+                    yuv[plane + i] =
+                        (128.0 + (-37.945 * r - 74.494 * g + 112.439 * b) / 256.0).round() as u8;
+                    yuv[2 * plane + i] =
+                        (128.0 + (112.439 * r - 94.154 * g - 18.285 * b) / 256.0).round() as u8;
+                }
+                self.w.write_all(b"FRAME\n")?;
+                self.w.write_all(&yuv)
             }
         }
     }
 
-    fn render_static_sprite(&mut self, x: i32, y: i32, sprite: &FixedSpriteData) {
-        let w = sprite.width();
-        let h = sprite.height();
-        for yi in 0..h {
-            for xi in 0..w {
-                let color = sprite.data[yi as usize][xi as usize];
-                self.set_pixel_alpha(xi + x, yi + y, color)
+    /// Flush and give back the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.w.flush()?;
+        Ok(self.w)
+    }
+}
+
+impl VideoWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        width: i32,
+        height: i32,
+        fps: u32,
+        format: VideoFormat,
+    ) -> io::Result<VideoWriter<BufWriter<File>>> {
+        VideoWriter::new(
+            BufWriter::new(File::create(path)?),
+            width,
+            height,
+            fps,
+            format,
+        )
+    }
+}
+
+fn check_frame_size(frame: &ImageBuffer, width: i32, height: i32) -> io::Result<()> {
+    if frame.width == width && frame.height == height {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Expected a {}x{} frame, not {}x{}",
+                width, height, frame.width, frame.height
+            ),
+        ))
+    }
+}
+
+/// How render_text draws each character cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextStyle {
+    /// Plain characters from a brightness ramp; works in any terminal or log file.
+    Ascii,
+    /// Upper-half-block characters with 24-bit ANSI foreground (top pixel) and background (bottom pixel) colors.
+    Ansi,
+}
+
+/// Brightness ramp for TextStyle::Ascii, darkest first.
+const ASCII_RAMP: &[u8] = b" .:-=+*#%@";
+
+/// Draw commands as text for a terminal, `columns` characters wide, e.g., to watch a game over ssh.
+/// The frame is area-resampled so that each character covers one pixel column and two pixel rows, which keeps the game's proportions in most terminal fonts.
+pub fn render_text(
+    commands: &[Drawable],
+    (width, height): (i32, i32),
+    columns: i32,
+    style: TextStyle,
+) -> String {
+    let mut img = ImageBuffer::alloc(width, height);
+    img.render(commands);
+    let columns = columns.max(1);
+    let rows = ((height * columns + width - 1) / width + 1) / 2;
+    let small = img.resized(Crop::full(width, height), columns, rows * 2, Resample::Area);
+
+    let mut output = String::new();
+    for row in 0..rows {
+        for col in 0..columns {
+            let top = small.get_pixel(col, row * 2).unwrap_or_else(Color::black);
+            let bottom = small
+                .get_pixel(col, row * 2 + 1)
+                .unwrap_or_else(Color::black);
+            match style {
+                TextStyle::Ascii => {
+                    let level = (top.luminance() + bottom.luminance()) / 2.0;
+                    let index = (level * (ASCII_RAMP.len() - 1) as f64).round() as usize;
+                    output.push(ASCII_RAMP[index.min(ASCII_RAMP.len() - 1)] as char);
+                }
+                TextStyle::Ansi => output.push_str(&format!(
+                    "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}",
+                    top.r, top.g, top.b, bottom.r, bottom.g, bottom.b
+                )),
             }
         }
+        if style == TextStyle::Ansi {
+            output.push_str("\x1b[0m");
+        }
+        output.push('\n');
     }
+    output
+}
 
-    pub fn render(&mut self, commands: &[Drawable]) {
+/// Options for SvgRenderer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgOptions {
+    /// Size of one game pixel in the output; the image is resolution-independent, so this only changes its default display size.
+    pub scale: f64,
+    /// Outline and label every tagged object (see Tag) on top of the picture.
+    pub annotate: bool,
+    /// Names for annotation labels, usually Simulation::object_kinds; class ids without a name are labelled by number.
+    pub kinds: Vec<String>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> SvgOptions {
+        SvgOptions {
+            scale: 1.0,
+            annotate: false,
+            kinds: Vec::new(),
+        }
+    }
+}
+
+/// Draws commands as an SVG document, e.g., for crisp figures in papers.
+/// Rectangles stay rectangles; sprites become one rectangle per horizontal run of same-colored pixels.
+/// Tagged commands are wrapped in a `<g>` with `data-class` and `data-instance` attributes, so objects can be picked out in a vector editor.
+pub struct SvgRenderer {
+    pub width: i32,
+    pub height: i32,
+    pub options: SvgOptions,
+}
+
+impl SvgRenderer {
+    pub fn new(width: i32, height: i32, options: SvgOptions) -> SvgRenderer {
+        SvgRenderer {
+            width,
+            height,
+            options,
+        }
+    }
+
+    /// The full SVG document for one frame.
+    pub fn render(&self, commands: &[Drawable]) -> String {
+        let mut output = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" shape-rendering=\"crispEdges\">\n",
+            f64::from(self.width) * self.options.scale,
+            f64::from(self.height) * self.options.scale,
+            self.width,
+            self.height
+        );
+        // Union of each object's bounding boxes, in the order they were first drawn.
+        let mut objects: Vec<(Tag, SvgBounds)> = Vec::new();
         for cmd in commands {
-            match cmd {
-                &Drawable::Clear(color) => {
-                    for pixel in self.data.chunks_exact_mut(4) {
-                        pixel[0] = color.r;
-                        pixel[1] = color.g;
-                        pixel[2] = color.b;
-                        pixel[3] = color.a;
-                    }
-                }
-                &Drawable::Rectangle { color, x, y, w, h } => {
-                    self.render_rectangle(color, x, y, w, h)
+            self.render_command(&mut output, cmd, None, &mut objects);
+        }
+        if self.options.annotate {
+            output.push_str("<g class=\"annotations\" fill=\"none\" stroke=\"#ff00ff\" stroke-width=\"0.5\" font-family=\"monospace\" font-size=\"4\">\n");
+            for &(tag, (x, y, w, h)) in &objects {
+                let name = match self.options.kinds.get(tag.class_id as usize - 1) {
+                    Some(kind) => svg_escape(kind),
+                    None => format!("class {}", tag.class_id),
+                };
+                output.push_str(&format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>\n<text x=\"{}\" y=\"{}\" fill=\"#ff00ff\" stroke=\"none\">{} {}</text>\n",
+                    x, y, w, h, x, y - 1, name, tag.instance_id
+                ));
+            }
+            output.push_str("</g>\n");
+        }
+        output.push_str("</svg>\n");
+        output
+    }
+
+    /// Render one frame straight to a file.
+    pub fn save<P: AsRef<Path>>(&self, commands: &[Drawable], path: P) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(self.render(commands).as_bytes())?;
+        file.flush()
+    }
+
+    fn render_command(
+        &self,
+        output: &mut String,
+        cmd: &Drawable,
+        tag: Option<Tag>,
+        objects: &mut Vec<(Tag, SvgBounds)>,
+    ) {
+        let bounds = match cmd {
+            &Drawable::Clear(color) => {
+                svg_rect(output, color, 0, 0, self.width, self.height);
+                None
+            }
+            &Drawable::Rectangle { color, x, y, w, h } => {
+                svg_rect(output, color, x, y, w, h);
+                Some((x, y, w, h))
+            }
+            &Drawable::StaticSprite {
+                x,
+                y,
+                data: ref sprite,
+            } => {
+                svg_sprite(output, x, y, &sprite.data);
+                Some((x, y, sprite.width(), sprite.height()))
+            }
+            &Drawable::DestructibleSprite(ref sprite) => {
+                svg_sprite(output, sprite.x, sprite.y, &sprite.data);
+                Some((sprite.x, sprite.y, sprite.width(), sprite.height()))
+            }
+            &Drawable::Tagged(inner_tag, ref inner) => {
+                output.push_str(&format!(
+                    "<g data-class=\"{}\" data-instance=\"{}\">\n",
+                    inner_tag.class_id, inner_tag.instance_id
+                ));
+                self.render_command(output, inner, Some(inner_tag), objects);
+                output.push_str("</g>\n");
+                None
+            }
+        };
+        // As in MaskBuffer, the innermost tag wins; class 0 is background.
+        if let (Some(tag), Some((x, y, w, h))) = (tag, bounds) {
+            if tag.class_id == 0 || w <= 0 || h <= 0 {
+                return;
+            }
+            match objects.iter().position(|&(t, _)| t == tag) {
+                Some(index) => {
+                    let b = &mut objects[index].1;
+                    let x1 = (b.0 + b.2).max(x + w);
+                    let y1 = (b.1 + b.3).max(y + h);
+                    b.0 = b.0.min(x);
+                    b.1 = b.1.min(y);
+                    b.2 = x1 - b.0;
+                    b.3 = y1 - b.1;
                 }
-                &Drawable::StaticSprite {
-                    x,
-                    y,
-                    data: ref sprite,
-                } => self.render_static_sprite(x, y, sprite),
-                &Drawable::DestructibleSprite(ref sprite) => {
-                    let w = sprite.width();
-                    let h = sprite.height();
-                    let (x, y) = sprite.position();
-                    for yi in 0..h {
-                        for xi in 0..w {
-                            let color = sprite.data[yi as usize][xi as usize];
-                            self.set_pixel_alpha(xi + x, yi + y, color)
-                        }
-                    }
+                None => objects.push((tag, (x, y, w, h))),
+            }
+        }
+    }
+}
+
+/// x, y, width and height.
+type SvgBounds = (i32, i32, i32, i32);
+
+fn svg_rect(output: &mut String, color: Color, x: i32, y: i32, w: i32, h: i32) {
+    if !color.is_visible() || w <= 0 || h <= 0 {
+        return;
+    }
+    output.push_str(&format!(
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#{:02x}{:02x}{:02x}\"",
+        x, y, w, h, color.r, color.g, color.b
+    ));
+    if !color.is_opaque() {
+        output.push_str(&format!(
+            " fill-opacity=\"{:.3}\"",
+            f64::from(color.a) / 255.0
+        ));
+    }
+    output.push_str("/>\n");
+}
+
+fn svg_sprite(output: &mut String, x: i32, y: i32, data: &[Vec<Color>]) {
+    for (yi, row) in data.iter().enumerate() {
+        let mut start = 0;
+        while start < row.len() {
+            let color = row[start];
+            let run = row[start..].iter().take_while(|&&c| c == color).count();
+            svg_rect(
+                output,
+                color,
+                x + start as i32,
+                y + yi as i32,
+                run as i32,
+                1,
+            );
+            start += run;
+        }
+    }
+}
+
+fn svg_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Keeps the last `depth` rendered frames (e.g., the 4 of DQN) in a ring buffer, so that observations can be stacked without copying every frame again each step.
+/// Frames that have not been pushed yet are all zeros.
+#[derive(Debug, Clone)]
+pub struct FrameStack {
+    pub width: i32,
+    pub height: i32,
+    /// Bytes per pixel: 1 for GrayscaleBuffer frames, 4 for ImageBuffer frames.
+    pub channels: usize,
+    frames: Vec<Vec<u8>>,
+    /// Index of the slot the next push overwrites, i.e., of the oldest frame.
+    next: usize,
+}
+
+impl FrameStack {
+    pub fn new(width: i32, height: i32, channels: usize, depth: usize) -> FrameStack {
+        assert!(depth > 0);
+        FrameStack {
+            width,
+            height,
+            channels,
+            frames: vec![vec![0; (width * height) as usize * channels]; depth],
+            next: 0,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// How many bytes write_channels_last and write_channels_first fill.
+    pub fn output_len(&self) -> usize {
+        self.depth() * self.frames[0].len()
+    }
+
+    /// Forget every frame, e.g., at the start of a new episode.
+    pub fn clear(&mut self) {
+        for frame in self.frames.iter_mut() {
+            for px in frame.iter_mut() {
+                *px = 0;
+            }
+        }
+        self.next = 0;
+    }
+
+    /// Replace the oldest frame with this one; it must be width * height * channels bytes.
+    pub fn push(&mut self, frame: &[u8]) {
+        {
+            let slot = &mut self.frames[self.next];
+            assert_eq!(slot.len(), frame.len());
+            slot.copy_from_slice(frame);
+        }
+        self.next = (self.next + 1) % self.frames.len();
+    }
+
+    pub fn push_grayscale(&mut self, frame: &GrayscaleBuffer) {
+        assert_eq!(self.channels, 1);
+        self.push(&frame.data);
+    }
+
+    pub fn push_image(&mut self, frame: &ImageBuffer) {
+        assert_eq!(self.channels, 4);
+        self.push(&frame.data);
+    }
+
+    /// Frames from oldest to newest.
+    fn ordered(&self) -> impl Iterator<Item = &Vec<u8>> {
+        let depth = self.frames.len();
+        (0..depth).map(move |i| &self.frames[(self.next + i) % depth])
+    }
+
+    /// Write the stack as a (height, width, depth * channels) array, oldest frame first along the last axis.
+    pub fn write_channels_last(&self, output: &mut [u8]) {
+        assert_eq!(output.len(), self.output_len());
+        let stride = self.depth() * self.channels;
+        for (i, frame) in self.ordered().enumerate() {
+            let offset = i * self.channels;
+            for (px, out) in frame.chunks(self.channels).zip(output.chunks_mut(stride)) {
+                out[offset..offset + self.channels].copy_from_slice(px);
+            }
+        }
+    }
+
+    /// Write the stack as a (depth * channels, height, width) array, oldest frame first.
+    pub fn write_channels_first(&self, output: &mut [u8]) {
+        assert_eq!(output.len(), self.output_len());
+        let plane = (self.width * self.height) as usize;
+        for (i, frame) in self.ordered().enumerate() {
+            for c in 0..self.channels {
+                let out = &mut output[(i * self.channels + c) * plane..][..plane];
+                for (o, px) in out.iter_mut().zip(frame.chunks(self.channels)) {
+                    *o = px[c];
                 }
             }
         }
-        // Done.
     }
 }
 
@@ -445,3 +1563,327 @@ pub fn load_digit_sprites(
 
     sprites.into_iter().rev().collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend_over() {
+        let red = Color::rgb(255, 0, 0);
+        let half_blue = Color::rgba(0, 0, 255, 128);
+        assert_eq!(red.blend_over(half_blue), red);
+        assert_eq!(Color::invisible().blend_over(red), red);
+        assert_eq!(half_blue.blend_over(Color::invisible()), half_blue);
+        assert_eq!(half_blue.blend_over(red), Color::rgba(127, 0, 128, 255));
+        // Two translucent layers are more opaque than either.
+        assert_eq!(half_blue.blend_over(half_blue).a, 191);
+
+        let mut img = ImageBuffer::alloc(2, 1);
+        img.render(&[
+            Drawable::Clear(Color::white()),
+            Drawable::rect(Color::black().with_alpha(64), 1, 0, 1, 1),
+        ]);
+        assert_eq!(img.get_pixel(0, 0), Some(Color::white()));
+        assert_eq!(img.get_pixel(1, 0), Some(Color::rgb(191, 191, 191)));
+        assert_eq!(img.get_pixel(2, 0), None);
+
+        let mut gray = GrayscaleBuffer::alloc(2, 1);
+        gray.render(&[
+            Drawable::Clear(Color::white()),
+            Drawable::rect(Color::black().with_alpha(64), 1, 0, 1, 1),
+        ]);
+        assert_eq!(gray.data, vec![255, 191]);
+    }
+
+    #[test]
+    fn test_resample() {
+        // A 4x2 image: two columns of 0 and two of 200, then 100 in the bottom row.
+        let mut gray = GrayscaleBuffer::alloc(4, 2);
+        gray.data = vec![0, 0, 200, 200, 100, 100, 100, 100];
+        let full = Crop::full(4, 2);
+
+        let nearest = gray.resized(full, 2, 1, Resample::Nearest);
+        assert_eq!(nearest.data, vec![100, 100]);
+        let area = gray.resized(full, 2, 1, Resample::Area);
+        assert_eq!(area.data, vec![50, 150]);
+        let bilinear = gray.resized(full, 2, 2, Resample::Bilinear);
+        assert_eq!(bilinear.data, vec![0, 200, 100, 100]);
+        // Growing with Area repeats pixels.
+        let area = gray.resized(full, 8, 2, Resample::Area);
+        assert_eq!(&area.data[0..8], &[0, 0, 0, 0, 200, 200, 200, 200]);
+
+        let cropped = gray.resized(Crop::new(2, -5, 10, 6), 1, 1, Resample::Area);
+        assert_eq!(cropped.data, vec![200]);
+        let empty = gray.resized(Crop::new(9, 9, 1, 1), 2, 2, Resample::Area);
+        assert_eq!(empty.data, vec![0; 4]);
+
+        let mut img = ImageBuffer::alloc(2, 2);
+        img.render(&[
+            Drawable::Clear(Color::black()),
+            Drawable::rect(Color::white(), 0, 0, 1, 2),
+        ]);
+        let small = img.resized(Crop::full(2, 2), 1, 1, Resample::Area);
+        assert_eq!(small.data, vec![128, 128, 128, 255]);
+    }
+
+    #[test]
+    fn test_frame_stack() {
+        let mut stack = FrameStack::new(2, 1, 1, 3);
+        assert_eq!(stack.output_len(), 6);
+        let mut output = vec![9; 6];
+        stack.write_channels_last(&mut output);
+        assert_eq!(output, vec![0; 6]);
+
+        for frame in &[[1, 2], [3, 4], [5, 6], [7, 8]] {
+            stack.push(frame);
+        }
+        stack.write_channels_last(&mut output);
+        assert_eq!(output, vec![3, 5, 7, 4, 6, 8]);
+        stack.write_channels_first(&mut output);
+        assert_eq!(output, vec![3, 4, 5, 6, 7, 8]);
+
+        let mut rgba = FrameStack::new(1, 2, 4, 2);
+        let mut img = ImageBuffer::alloc(1, 2);
+        img.render(&[Drawable::Clear(Color::rgb(1, 2, 3))]);
+        rgba.push_image(&img);
+        let mut output = vec![0; 16];
+        rgba.write_channels_first(&mut output);
+        assert_eq!(
+            output,
+            vec![0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 255, 255]
+        );
+        rgba.clear();
+        rgba.write_channels_last(&mut output);
+        assert_eq!(output, vec![0; 16]);
+    }
+
+    #[test]
+    fn test_export() {
+        let mut img = ImageBuffer::alloc(3, 2);
+        img.render(&[
+            Drawable::Clear(Color::white()),
+            Drawable::rect(Color::rgb(255, 0, 0), 0, 0, 1, 1),
+        ]);
+
+        let mut png_bytes = Vec::new();
+        img.write_png(&mut png_bytes).unwrap();
+        let decoded = FixedSpriteData::load_png(&png_bytes);
+        assert_eq!(decoded.data[0][0], Color::rgb(255, 0, 0));
+        assert_eq!(decoded.data[1][2], Color::white());
+
+        let mut gif_bytes = Vec::new();
+        {
+            let mut gif = GifWriter::new(&mut gif_bytes, 3, 2, 50).unwrap();
+            gif.write_frame(&img).unwrap();
+            gif.write_frame(&img).unwrap();
+            assert!(gif.write_frame(&ImageBuffer::alloc(2, 2)).is_err());
+        }
+        assert_eq!(&gif_bytes[0..6], b"GIF89a");
+
+        let mut raw = VideoWriter::new(Vec::new(), 3, 2, 60, VideoFormat::RawRgb).unwrap();
+        raw.write_frame(&img).unwrap();
+        let raw = raw.finish().unwrap();
+        assert_eq!(raw.len(), 3 * 2 * 3);
+        assert_eq!(&raw[0..6], &[255, 0, 0, 255, 255, 255]);
+
+        let mut y4m = VideoWriter::new(Vec::new(), 3, 2, 60, VideoFormat::Y4m).unwrap();
+        y4m.write_frame(&img).unwrap();
+        let y4m = y4m.finish().unwrap();
+        let header = b"YUV4MPEG2 W3 H2 F60:1 Ip A1:1 C444\nFRAME\n";
+        assert_eq!(&y4m[0..header.len()], &header[..]);
+        // White is full-scale luma and neutral chroma.
+        let planes = &y4m[header.len()..];
+        assert_eq!(planes.len(), 3 * 2 * 3);
+        assert_eq!((planes[1], planes[6 + 1], planes[12 + 1]), (235, 128, 128));
+    }
+
+    #[test]
+    fn test_render_text() {
+        let commands = vec![
+            Drawable::Clear(Color::black()),
+            Drawable::rect(Color::white(), 0, 0, 2, 4),
+        ];
+        let ascii = render_text(&commands, (4, 4), 4, TextStyle::Ascii);
+        assert_eq!(ascii, "@@  \n@@  \n");
+
+        let ansi = render_text(&commands, (4, 4), 2, TextStyle::Ansi);
+        let white = "\x1b[38;2;255;255;255m\x1b[48;2;255;255;255m\u{2580}";
+        let black = "\x1b[38;2;0;0;0m\x1b[48;2;0;0;0m\u{2580}";
+        assert_eq!(ansi, format!("{}{}\x1b[0m\n", white, black));
+    }
+
+    #[test]
+    fn test_svg() {
+        let sprite = FixedSpriteData::new(vec![vec![
+            Color::white(),
+            Color::white(),
+            Color::invisible(),
+            Color::rgb(255, 0, 0),
+        ]]);
+        let commands = vec![
+            Drawable::Clear(Color::black()),
+            Drawable::rect(Color::white().with_alpha(128), 0, 0, 2, 3).tagged(1, 5),
+            Drawable::sprite(1, 4, sprite).tagged(2, 1),
+        ];
+
+        let plain = SvgRenderer::new(8, 6, SvgOptions::default()).render(&commands);
+        assert!(plain.starts_with("<svg "));
+        assert!(plain.ends_with("</svg>\n"));
+        assert!(plain.contains("viewBox=\"0 0 8 6\""));
+        assert!(plain.contains("<rect x=\"0\" y=\"0\" width=\"8\" height=\"6\" fill=\"#000000\"/>"));
+        assert!(plain.contains("fill=\"#ffffff\" fill-opacity=\"0.502\"/>"));
+        // The sprite's white pixels are one run, and its invisible pixel is skipped.
+        assert!(plain.contains("<rect x=\"1\" y=\"4\" width=\"2\" height=\"1\" fill=\"#ffffff\"/>"));
+        assert!(plain.contains("<rect x=\"4\" y=\"4\" width=\"1\" height=\"1\" fill=\"#ff0000\"/>"));
+        assert_eq!(plain.matches("<rect").count(), 4);
+        assert!(plain.contains("<g data-class=\"2\" data-instance=\"1\">"));
+        assert!(!plain.contains("annotations"));
+
+        let options = SvgOptions {
+            scale: 2.0,
+            annotate: true,
+            kinds: vec!["paddle".to_owned()],
+        };
+        let annotated = SvgRenderer::new(8, 6, options).render(&commands);
+        assert!(annotated.contains("width=\"16\" height=\"12\""));
+        assert!(annotated.contains(">paddle 5</text>"));
+        assert!(annotated.contains(">class 2 1</text>"));
+        assert!(annotated.contains("<rect x=\"1\" y=\"4\" width=\"4\" height=\"1\"/>"));
+    }
+
+    #[test]
+    fn test_canvas_clipping() {
+        // Off-screen parts of shapes are dropped rather than wrapped onto other rows.
+        let sprite = FixedSpriteData::new(vec![vec![Color::white(); 3]; 3]);
+        let commands = vec![
+            Drawable::Clear(Color::black()),
+            Drawable::rect(Color::white(), -2, 0, 3, 1),
+            Drawable::rect(Color::white(), 3, 1, 5, 1),
+            Drawable::sprite(2, 2, sprite),
+        ];
+        let mut gray = GrayscaleBuffer::alloc(4, 3);
+        gray.render(&commands);
+        let expected = [[255, 0, 0, 0], [0, 0, 0, 255], [0, 0, 255, 255]].concat();
+        assert_eq!(gray.data, expected);
+
+        let mut img = ImageBuffer::alloc(4, 3);
+        img.render(&commands);
+        let img_gray: Vec<u8> = img.data.chunks(4).map(|px| px[0]).collect();
+        assert_eq!(img_gray, expected);
+    }
+
+    /// Only the required methods, so it draws pixel by pixel with the provided ones.
+    struct PixelCanvas<C: Canvas>(C);
+    impl<C: Canvas> Canvas for PixelCanvas<C> {
+        fn size(&self) -> (i32, i32) {
+            self.0.size()
+        }
+        fn clear(&mut self, color: Color, tag: Tag) {
+            self.0.clear(color, tag)
+        }
+        fn put_pixel(&mut self, x: i32, y: i32, color: Color, tag: Tag) {
+            self.0.put_pixel(x, y, color, tag)
+        }
+    }
+
+    #[test]
+    fn test_spans_match_pixels() {
+        let red = Color::rgb(255, 0, 0);
+        let sprite = FixedSpriteData::new(vec![
+            vec![red, Color::invisible(), red.with_alpha(100)],
+            vec![Color::white(), red, Color::invisible()],
+        ]);
+        let commands = vec![
+            Drawable::Clear(Color::rgb(0, 0, 80)),
+            Drawable::rect(Color::white().with_alpha(60), -3, 1, 6, 9).tagged(1, 1),
+            Drawable::rect(red, 5, -2, 20, 3),
+            Drawable::sprite(-1, -1, sprite.clone()).tagged(2, 1),
+            Drawable::sprite(6, 4, sprite.clone()).tagged(2, 2),
+            Drawable::sprite(3, 2, sprite.clone()).tagged(2, 3),
+            Drawable::DestructibleSprite(SpriteData {
+                x: 1,
+                y: 3,
+                data: sprite.data.to_vec(),
+            }),
+        ];
+        let mut spans = ImageBuffer::alloc(7, 5);
+        spans.render(&commands);
+        let mut pixels = PixelCanvas(ImageBuffer::alloc(7, 5));
+        pixels.render(&commands);
+        assert_eq!(spans.data, pixels.0.data);
+
+        let mut spans = GrayscaleBuffer::alloc(7, 5);
+        spans.render(&commands);
+        let mut pixels = PixelCanvas(GrayscaleBuffer::alloc(7, 5));
+        pixels.render(&commands);
+        assert_eq!(spans.data, pixels.0.data);
+
+        let mut spans = MaskBuffer::alloc(7, 5);
+        spans.render(&commands);
+        let mut pixels = PixelCanvas(MaskBuffer::alloc(7, 5));
+        pixels.render(&commands);
+        assert_eq!(spans.class_ids, pixels.0.class_ids);
+        assert_eq!(spans.instance_ids, pixels.0.instance_ids);
+
+        // Clones share the cached renders.
+        assert_eq!(sprite.rgba().len(), 6 * 4);
+        assert_eq!(
+            sprite.clone().grayscale().as_ptr(),
+            sprite.grayscale().as_ptr()
+        );
+        assert_eq!(sprite.visible(), &[true, false, true, true, true, false]);
+    }
+
+    #[test]
+    fn test_palette_buffer() {
+        let red = Color::rgb(255, 0, 0);
+        let commands = vec![
+            Drawable::Clear(Color::black()),
+            Drawable::rect(red, 0, 0, 2, 1),
+            Drawable::rect(Color::white().with_alpha(0), 0, 0, 3, 1),
+            Drawable::rect(Color::white(), 1, 1, 2, 1),
+        ];
+        let mut buffer = PaletteBuffer::alloc(3, 2, vec![Color::black(), Color::white()]);
+        buffer.render(&commands);
+        assert_eq!(buffer.palette, vec![Color::black(), Color::white(), red]);
+        assert_eq!(buffer.data, vec![2, 2, 0, 0, 1, 1]);
+
+        // Translucent colors are blended, then mapped to the closest entry.
+        buffer.render(&[Drawable::rect(Color::rgba(0, 0, 0, 64), 0, 0, 1, 1)]);
+        assert_eq!(buffer.palette.len(), 3);
+        assert_eq!(buffer.data[0], 2);
+
+        // A full palette maps new colors to the closest entry.
+        let full: Vec<Color> = (0..256).map(|i| Color::rgb(i as u8, 0, 0)).collect();
+        let mut buffer = PaletteBuffer::alloc(1, 1, full);
+        assert_eq!(buffer.index_of(Color::rgb(200, 3, 0)), 200);
+        assert_eq!(buffer.palette.len(), 256);
+    }
+
+    #[test]
+    fn test_mask_buffer() {
+        let sprite = FixedSpriteData::new(vec![vec![Color::white(), Color::invisible()]]);
+        let commands = vec![
+            Drawable::Clear(Color::black()),
+            Drawable::rect(Color::white(), 0, 0, 3, 1).tagged(1, 7),
+            Drawable::sprite(1, 0, sprite).tagged(2, 1),
+            Drawable::rect(Color::white(), 0, 1, 1, 1).tagged(1, 8),
+            Drawable::rect(Color::black(), 0, 1, 1, 1),
+        ];
+        let mut mask = MaskBuffer::alloc(3, 2);
+        mask.render(&commands);
+        assert_eq!(mask.class_ids, vec![1, 2, 1, 0, 0, 0]);
+        assert_eq!(mask.instance_ids, vec![7, 1, 7, 0, 0, 0]);
+
+        // Tags do not change the colors drawn.
+        let mut tagged = ImageBuffer::alloc(3, 2);
+        tagged.render(&commands);
+        let mut plain = ImageBuffer::alloc(3, 2);
+        plain.render(&[
+            Drawable::Clear(Color::black()),
+            Drawable::rect(Color::white(), 0, 0, 3, 1),
+        ]);
+        assert_eq!(tagged.data, plain.data);
+    }
+}
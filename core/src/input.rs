@@ -1,6 +1,6 @@
-/// Think NES-style controls: directions, and two buttons.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-#[repr(C)]
+/// Think NES-style controls: directions, two buttons, and an optional analog paddle.
+/// This only crosses the C API as JSON, so its layout is Rust's own.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Input {
     pub left: bool,
     pub right: bool,
@@ -8,6 +8,9 @@ pub struct Input {
     pub down: bool,
     pub button1: bool,
     pub button2: bool,
+    /// Where to put an analog paddle, from 0.0 (far left) to 1.0 (far right); only games whose ActionSpace has a paddle read it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paddle: Option<f32>,
 }
 
 impl Default for Input {
@@ -19,6 +22,7 @@ impl Default for Input {
             down: false,
             button1: false,
             button2: false,
+            paddle: None,
         }
     }
 }
@@ -30,7 +34,43 @@ impl Input {
         AleAction::from_int(x).map(|a| a.to_input())
     }
     pub fn is_empty(self) -> bool {
-        !self.left && !self.right && !self.up && !self.down && !self.button1 && !self.button2
+        !self.left
+            && !self.right
+            && !self.up
+            && !self.down
+            && !self.button1
+            && !self.button2
+            && self.paddle.is_none()
+    }
+    /// The same buttons, with a paddle position.
+    pub fn with_paddle(mut self, position: f32) -> Input {
+        self.paddle = Some(position);
+        self
+    }
+    /// Pack the six buttons into the low bits of a byte: left, right, up, down, button1, button2.
+    /// The paddle is not included; see replay::Replay::paddle for how recordings keep it.
+    pub fn to_bits(self) -> u8 {
+        (self.left as u8)
+            | (self.right as u8) << 1
+            | (self.up as u8) << 2
+            | (self.down as u8) << 3
+            | (self.button1 as u8) << 4
+            | (self.button2 as u8) << 5
+    }
+    /// Inverse of to_bits; returns None if any of the two high bits are set.
+    pub fn from_bits(bits: u8) -> Option<Input> {
+        if bits >> 6 != 0 {
+            return None;
+        }
+        Some(Input {
+            left: bits & 1 != 0,
+            right: bits & (1 << 1) != 0,
+            up: bits & (1 << 2) != 0,
+            down: bits & (1 << 3) != 0,
+            button1: bits & (1 << 4) != 0,
+            button2: bits & (1 << 5) != 0,
+            paddle: None,
+        })
     }
 }
 
@@ -102,6 +142,34 @@ impl AleAction {
             _ => None,
         }
     }
+    /// A snake_case name for this action, e.g., "noop" or "down_left_fire".
+    pub fn name(self) -> String {
+        let input = self.to_input();
+        let parts = [
+            (input.up, "up"),
+            (input.down, "down"),
+            (input.right, "right"),
+            (input.left, "left"),
+            (input.button1, "fire"),
+        ];
+        let pressed: Vec<&str> = parts
+            .iter()
+            .filter(|&&(on, _)| on)
+            .map(|&(_, name)| name)
+            .collect();
+        if pressed.is_empty() {
+            "noop".to_owned()
+        } else {
+            pressed.join("_")
+        }
+    }
+    /// The ALE action that presses exactly these buttons, if there is one; the paddle is ignored.
+    pub fn from_input(input: Input) -> Option<AleAction> {
+        let bits = input.to_bits();
+        (0..18)
+            .filter_map(AleAction::from_int)
+            .find(|a| a.to_input().to_bits() == bits)
+    }
     pub fn to_input(self) -> Input {
         let mut input = Input::default();
         match self {
@@ -178,9 +246,93 @@ impl AleAction {
     }
 }
 
+/// One discrete action that a game responds to, described for agents and humans; see ActionSpace.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Action {
+    /// A short, game-specific name such as "jump", unique within its ActionSpace.
+    pub name: String,
+    /// The buttons this action presses.
+    pub input: Input,
+    /// Game-specific situations in which this action's button does nothing, e.g., "no_jumps_left"; empty if it always has an effect.
+    pub noop_when: Vec<String>,
+}
+
+impl Action {
+    /// An ALE action under its ALE name, e.g., "up_right_fire".
+    pub fn from_ale(action: AleAction) -> Action {
+        Action {
+            name: action.name(),
+            input: action.to_input(),
+            noop_when: Vec::new(),
+        }
+    }
+    pub fn with_name(mut self, name: &str) -> Action {
+        self.name = name.to_owned();
+        self
+    }
+    pub fn noop_when(mut self, mode: &str) -> Action {
+        self.noop_when.push(mode.to_owned());
+        self
+    }
+}
+
+/// The controls a game responds to, declared by Simulation::action_space: a list of discrete actions that an agent can choose between by index, and whether the game also reads Input::paddle.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionSpace {
+    /// Each discrete action.
+    pub actions: Vec<Action>,
+    /// Whether the game reads an analog paddle position.
+    pub paddle: bool,
+}
+
+impl ActionSpace {
+    /// Wrap already-described actions, with no paddle.
+    pub fn new(actions: Vec<Action>) -> ActionSpace {
+        ActionSpace {
+            actions,
+            paddle: false,
+        }
+    }
+    /// One discrete action per ALE action, in the same order and under their ALE names, and no paddle.
+    pub fn from_ale(actions: &[AleAction]) -> ActionSpace {
+        ActionSpace::new(actions.iter().map(|&a| Action::from_ale(a)).collect())
+    }
+    pub fn with_paddle(mut self) -> ActionSpace {
+        self.paddle = true;
+        self
+    }
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+    /// The buttons for a discrete action, or None if the index is out of range.
+    pub fn get(&self, index: usize) -> Option<Input> {
+        self.actions.get(index).map(|a| a.input)
+    }
+    /// The index of the discrete action that presses exactly these buttons; the paddle is ignored.
+    pub fn index_of(&self, input: Input) -> Option<usize> {
+        let bits = input.to_bits();
+        self.actions.iter().position(|a| a.input.to_bits() == bits)
+    }
+    /// The index of the discrete action with this name.
+    pub fn index_of_name(&self, name: &str) -> Option<usize> {
+        self.actions.iter().position(|a| a.name == name)
+    }
+    /// The ALE action for each discrete action, for agents that only speak ALE; None where there isn't one.
+    pub fn to_ale(&self) -> Vec<Option<AleAction>> {
+        self.actions
+            .iter()
+            .map(|a| AleAction::from_input(a.input))
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json;
 
     #[test]
     pub fn test_ale_to_from_int() {
@@ -191,4 +343,70 @@ mod tests {
         }
     }
 
+    #[test]
+    pub fn test_input_to_from_bits() {
+        for bits in 0..64 {
+            let input = Input::from_bits(bits).unwrap();
+            assert_eq!(bits, input.to_bits());
+        }
+        assert_eq!(None, Input::from_bits(64));
+        assert_eq!(0b10001, AleAction::LEFTFIRE.to_input().to_bits());
+    }
+
+    #[test]
+    pub fn test_ale_from_input() {
+        for i in 0..=17 {
+            let action = AleAction::from_int(i).unwrap();
+            assert_eq!(Some(action), AleAction::from_input(action.to_input()));
+            assert_eq!(
+                Some(action),
+                AleAction::from_input(action.to_input().with_paddle(0.5))
+            );
+        }
+        let button2 = Input {
+            button2: true,
+            ..Input::default()
+        };
+        assert_eq!(None, AleAction::from_input(button2));
+    }
+
+    #[test]
+    pub fn test_ale_names() {
+        assert_eq!("noop", AleAction::NOOP.name());
+        assert_eq!("fire", AleAction::FIRE.name());
+        assert_eq!("up_right", AleAction::UPRIGHT.name());
+        assert_eq!("down_left_fire", AleAction::DOWNLEFTFIRE.name());
+        let mut names: Vec<String> = (0..=17)
+            .map(|i| AleAction::from_int(i).unwrap().name())
+            .collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(18, names.len());
+    }
+
+    #[test]
+    pub fn test_action_space() {
+        let ale = [AleAction::NOOP, AleAction::FIRE, AleAction::LEFT];
+        let space = ActionSpace::from_ale(&ale).with_paddle();
+        assert_eq!(3, space.len());
+        assert!(space.paddle);
+        assert_eq!(Some(AleAction::LEFT.to_input()), space.get(2));
+        assert_eq!(None, space.get(3));
+        assert_eq!(Some(1), space.index_of(AleAction::FIRE.to_input()));
+        assert_eq!(None, space.index_of(AleAction::RIGHT.to_input()));
+        assert_eq!(Some(2), space.index_of_name("left"));
+        assert_eq!(None, space.index_of_name("right"));
+        let ale_again: Vec<AleAction> = space.to_ale().into_iter().map(|a| a.unwrap()).collect();
+        assert_eq!(ale.to_vec(), ale_again);
+
+        // Inputs saved before the paddle existed still load.
+        let old = r#"{"left":true,"right":false,"up":false,"down":false,"button1":false,"button2":false}"#;
+        let input: Input = serde_json::from_str(old).unwrap();
+        assert_eq!(None, input.paddle);
+        let json = serde_json::to_string(&input.with_paddle(0.25)).unwrap();
+        assert_eq!(
+            Some(0.25),
+            serde_json::from_str::<Input>(&json).unwrap().paddle
+        );
+    }
 }
@@ -2,21 +2,41 @@ extern crate serde;
 extern crate serde_json;
 #[macro_use]
 extern crate serde_derive;
+extern crate gif;
 extern crate png;
+extern crate rand;
 
 pub mod collision;
 pub mod graphics;
 pub mod random;
+pub mod replay;
 
 mod input;
+pub use input::Action;
+pub use input::ActionSpace;
 pub use input::AleAction;
 pub use input::Input;
 
 mod direction;
 pub use direction::Direction;
 
+mod event;
+pub use event::{Event, EventLog};
+
+mod objects;
+pub use objects::{encode_objects, object_row_width, Object};
+
+mod stable_hash;
+pub use stable_hash::{stable_hash, StableHasher};
+
+mod step_wrapper;
+pub use step_wrapper::{StepWrapper, StepWrapperConfig};
+
 extern crate rand_core;
 
+use std::error::Error;
+use std::fmt;
+
 /// This enum defines failure conditions for a query_json call.
 #[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
 pub enum QueryError {
@@ -31,31 +51,123 @@ impl From<serde_json::Error> for QueryError {
     }
 }
 
+/// This enum defines failure conditions for loading a Simulation or State, e.g., from JSON.
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Clone)]
+pub enum LoadError {
+    /// The input was not valid JSON, or did not match the expected structure.
+    ParseError(String),
+    /// The input was well-formed, but describes something that cannot be simulated.
+    InvalidConfig(String),
+    /// There is no game by this name; `known` lists the names that would have worked.
+    UnknownGame { name: String, known: Vec<String> },
+}
+
+impl LoadError {
+    /// A stable integer for each kind of error, for use across the C API; zero is reserved for "no error".
+    pub fn code(&self) -> i32 {
+        match self {
+            LoadError::ParseError(_) => 1,
+            LoadError::InvalidConfig(_) => 2,
+            LoadError::UnknownGame { .. } => 3,
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::ParseError(msg) => write!(f, "Could not parse JSON: {}", msg),
+            LoadError::InvalidConfig(msg) => write!(f, "Invalid config: {}", msg),
+            LoadError::UnknownGame { name, known } => {
+                write!(f, "Unknown game: `{}`. Try any of {:?}.", name, known)
+            }
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+impl From<serde_json::Error> for LoadError {
+    fn from(e: serde_json::Error) -> LoadError {
+        LoadError::ParseError(format!("{}", e))
+    }
+}
+
+/// What happened during one call to State::step.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct StepResult {
+    /// The change in score.
+    pub reward: i32,
+    /// Did the player lose a life?
+    pub life_lost: bool,
+    /// Did the player finish a level (and thereby start the next one)?
+    pub level_complete: bool,
+    /// Are lives now < 0?
+    pub game_over: bool,
+    /// Everything the game logged during the step, e.g., a "brick_hit"; stepping drains these from the state's EventLog.
+    pub events: Vec<Event>,
+}
+
+impl StepResult {
+    /// Fill in the fields every game shares by comparing a state to its score and lives from before the step.
+    pub fn diff(score_before: i32, lives_before: i32, after: &State) -> StepResult {
+        StepResult {
+            reward: after.score() - score_before,
+            life_lost: after.lives() < lives_before,
+            level_complete: false,
+            game_over: after.lives() < 0,
+            events: Vec::new(),
+        }
+    }
+}
+
 /// This trait models a single frame state for a Simulation.
-pub trait State {
+/// States must be Send so that batches of them can be stepped on worker threads.
+pub trait State: Send {
     /// When < 0, this state should be replaced with a call to new_game() on the simulation.
     fn lives(&self) -> i32;
     /// Get the score from the game, i32 allows for negative scores.
     fn score(&self) -> i32;
     /// To update internally to the next state, we pass buttons to internal logic.
     fn update_mut(&mut self, buttons: Input);
+    /// Like update_mut, but reports the reward, lives and game-specific events of the step.
+    /// The events are drained, so they include any logged by earlier update_mut calls that nobody drained.
+    fn step(&mut self, buttons: Input) -> StepResult;
+    /// Remove and return the events logged by update_mut since the last call (or step), oldest first.
+    fn drain_events(&mut self) -> Vec<Event>;
+    /// Describe the entities in this frame (player, enemies, projectiles...) for agents that learn from objects instead of pixels.
+    fn objects(&self) -> Vec<Object>;
     /// Any state can create a vector of drawable objects to present itself.
     fn draw(&self) -> Vec<graphics::Drawable>;
+    /// A game-specific plain-text picture of this state, e.g., Amidar's tile grid, for debugging in a terminal.
+    fn to_text(&self) -> String;
     /// Any state can serialize to JSON String.
     fn to_json(&self) -> String;
     /// Submit a query to this state object, returning a JSON String or error message.
     fn query_json(&self, query: &str, args: &serde_json::Value) -> Result<String, QueryError>;
+    /// Copy this state, e.g., to roll the copy forward without disturbing the original; much cheaper than a JSON round trip.
+    fn clone_box(&self) -> Box<State>;
+    /// A hash of what to_json would save (see stable_hash); it is the same across runs, so it can key transposition tables.
+    fn state_hash(&self) -> u64;
+}
+
+impl Clone for Box<State> {
+    fn clone(&self) -> Box<State> {
+        self.clone_box()
+    }
 }
 
 /// This trait models a simulation or game. It knows how to start a new game, and to declare its size before any gameplay starts.
 pub trait Simulation {
     /// Seed simulation.
     fn reset_seed(&mut self, seed: u32);
+    /// Seed simulation from 64 bits, expanded so that nearby seeds are unrelated; see random::Gen::new_from_seed_u64.
+    fn reset_seed_u64(&mut self, seed: u64);
 
     /// Generate a new State. This is in a Box<State> because it may be 1 of many unknown types as far as calling code is concerned.
     fn new_game(&mut self) -> Box<State>;
     /// Generate a new State from JSON String (usually modified from a dump of State::to_json).
-    fn new_state_from_json(&self, json: &str) -> Result<Box<State>, serde_json::Error>;
+    fn new_state_from_json(&self, json: &str) -> Result<Box<State>, LoadError>;
 
     /// Return a tuple of game size in pixels, e.g., (100,100).
     fn game_size(&self) -> (i32, i32);
@@ -64,8 +176,22 @@ pub trait Simulation {
     fn to_json(&self) -> String;
     /// This deserializes the "config" for a game from json.
     /// Generate new state and new config from JSON String.
-    fn from_json(&self, json: &str) -> Result<Box<Simulation>, serde_json::Error>;
+    fn from_json(&self, json: &str) -> Result<Box<Simulation>, LoadError>;
 
     /// Legal action set:
     fn legal_action_set(&self) -> Vec<AleAction>;
+    /// The discrete actions (and analog controls) this game responds to, with names and the situations where each does nothing.
+    /// The discrete actions match legal_action_set, in the same order.
+    fn action_space(&self) -> ActionSpace;
+
+    /// Every kind of Object that this game's states may report, in a fixed order for encode_objects.
+    /// Objects of the kind at index i are drawn inside a Drawable::Tagged with class id i + 1; 0 is background.
+    fn object_kinds(&self) -> Vec<String>;
+
+    /// Every color this game draws, in a fixed order, so that PaletteBuffer indices mean the same thing in every frame.
+    fn palette(&self) -> Vec<graphics::Color>;
+
+    /// Check this config for problems that would cause new_game to panic or misbehave.
+    /// Returns a human-readable description of each problem; an empty list means the config looks fine.
+    fn validate(&self) -> Vec<String>;
 }
@@ -0,0 +1,118 @@
+use serde_json;
+
+/// One entity in a game (a brick, an enemy, the player...) described symbolically rather than as pixels; see State::objects.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Object {
+    /// A short, game-specific name such as "brick"; Simulation::object_kinds lists every kind a game produces.
+    pub kind: String,
+    /// Left edge of the bounding box, in screen pixels.
+    pub x: i32,
+    /// Top edge of the bounding box, in screen pixels.
+    pub y: i32,
+    /// Width of the bounding box, in screen pixels.
+    pub w: i32,
+    /// Height of the bounding box, in screen pixels.
+    pub h: i32,
+    /// Screen pixels per frame, for games that track it.
+    pub velocity: Option<(f64, f64)>,
+    /// Details of the object that depend on its kind, e.g., the row, column and points of a brick.
+    pub attributes: serde_json::Value,
+}
+
+impl Object {
+    /// An object with no velocity and no attributes.
+    pub fn new(kind: &str, x: i32, y: i32, w: i32, h: i32) -> Object {
+        Object {
+            kind: kind.to_owned(),
+            x,
+            y,
+            w,
+            h,
+            velocity: None,
+            attributes: serde_json::Value::Null,
+        }
+    }
+
+    pub fn with_velocity(mut self, vx: f64, vy: f64) -> Object {
+        self.velocity = Some((vx, vy));
+        self
+    }
+
+    pub fn with_attributes(mut self, attributes: serde_json::Value) -> Object {
+        self.attributes = attributes;
+        self
+    }
+}
+
+/// How many numbers encode_objects writes per object, given how many kinds there are.
+pub fn object_row_width(num_kinds: usize) -> usize {
+    // present, one-hot kind, x, y, w, h, vx, vy.
+    1 + num_kinds + 6
+}
+
+/// Flatten a list of objects into a fixed-size, row-major (max_objects, object_row_width) tensor, e.g., for a neural network.
+///
+/// Each row is a presence flag, a one-hot encoding of the object's kind (by its index in `kinds`), its bounding box and its velocity (zero when unknown).
+/// Objects past max_objects are dropped, and unused rows are all zeros. Attributes are not encoded.
+pub fn encode_objects(objects: &[Object], kinds: &[String], max_objects: usize) -> Vec<f32> {
+    let width = object_row_width(kinds.len());
+    let mut output = vec![0.0; width * max_objects];
+    for (obj, row) in objects.iter().zip(output.chunks_mut(width)) {
+        row[0] = 1.0;
+        if let Some(index) = kinds.iter().position(|k| *k == obj.kind) {
+            row[1 + index] = 1.0;
+        }
+        let (vx, vy) = obj.velocity.unwrap_or((0.0, 0.0));
+        let rest = &mut row[1 + kinds.len()..];
+        rest[0] = obj.x as f32;
+        rest[1] = obj.y as f32;
+        rest[2] = obj.w as f32;
+        rest[3] = obj.h as f32;
+        rest[4] = vx as f32;
+        rest[5] = vy as f32;
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_encode_objects() {
+        let kinds = vec!["ball".to_owned(), "paddle".to_owned()];
+        let objects = vec![
+            Object::new("paddle", 1, 2, 3, 4),
+            Object::new("ball", 5, 6, 7, 8)
+                .with_velocity(-1.0, 0.5)
+                .with_attributes(json!({"id": 0})),
+            Object::new("brick", 0, 0, 1, 1),
+        ];
+        let width = object_row_width(kinds.len());
+        assert_eq!(width, 9);
+
+        let tensor = encode_objects(&objects, &kinds, 4);
+        assert_eq!(tensor.len(), 4 * width);
+        assert_eq!(
+            &tensor[0..width],
+            &[1.0, 0.0, 1.0, 1.0, 2.0, 3.0, 4.0, 0.0, 0.0]
+        );
+        assert_eq!(
+            &tensor[width..2 * width],
+            &[1.0, 1.0, 0.0, 5.0, 6.0, 7.0, 8.0, -1.0, 0.5]
+        );
+        // Unknown kinds are still present, just without a kind.
+        assert_eq!(
+            &tensor[2 * width..3 * width],
+            &[1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0]
+        );
+        assert!(tensor[3 * width..].iter().all(|&x| x == 0.0));
+
+        // Too many objects are truncated.
+        assert_eq!(
+            encode_objects(&objects, &kinds, 1),
+            tensor[0..width].to_vec()
+        );
+    }
+}
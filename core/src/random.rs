@@ -1,4 +1,5 @@
 use rand_core::{impls, Error, RngCore};
+use std::collections::BTreeMap;
 
 /// This implementation is a xoroshiro128+ that is serde serializable.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,6 +43,114 @@ impl Gen {
     pub fn reset_seed(&mut self, seed: u32) {
         self.state = [0x193a6754a8a7d469 ^ (seed as u64), 0x97830e05113ba7bb]
     }
+
+    /// Create a generator from a 128-bit seed, expanded with SplitMix64 so that nearby seeds give unrelated streams.
+    /// Different seeds always give different generators. new_from_seed keeps its old mapping so earlier runs can be reproduced.
+    pub fn new_from_seed_u128(seed: u128) -> Gen {
+        let mut x = seed as u64;
+        let s0 = splitmix64(&mut x);
+        x ^= (seed >> 64) as u64;
+        let s1 = splitmix64(&mut x);
+        if s0 == 0 && s1 == 0 {
+            // xoroshiro128+ only ever returns zeros from an all-zero state.
+            return Gen::new([0, SPLITMIX64_GAMMA]);
+        }
+        Gen::new([s0, s1])
+    }
+    /// Create a generator from a 64-bit seed; see new_from_seed_u128.
+    pub fn new_from_seed_u64(seed: u64) -> Gen {
+        Gen::new_from_seed_u128(u128::from(seed))
+    }
+    pub fn reset_seed_u64(&mut self, seed: u64) {
+        *self = Gen::new_from_seed_u64(seed)
+    }
+
+    /// A child generator for the stream named `label`, e.g., "enemies", without advancing this one.
+    /// The same state and label always give the same child, in every version of toybox: the label is hashed with 64-bit FNV-1a and mixed into this state with SplitMix64.
+    pub fn fork(&self, label: &str) -> Gen {
+        self.fork_indexed(label, 0)
+    }
+    /// Like fork, but for one of many streams with the same name, e.g., one per enemy.
+    pub fn fork_indexed(&self, label: &str, index: u64) -> Gen {
+        let mut x = fnv1a64(label.as_bytes()) ^ index;
+        let k0 = splitmix64(&mut x);
+        let k1 = splitmix64(&mut x);
+        let seed = (u128::from(self.state[1] ^ k1) << 64) | u128::from(self.state[0] ^ k0);
+        Gen::new_from_seed_u128(seed)
+    }
+}
+
+const SPLITMIX64_GAMMA: u64 = 0x9e3779b97f4a7c15;
+
+/// One step of SplitMix64 (Steele, Lea and Flood, 2014): advance x and return a well-mixed function of it.
+fn splitmix64(x: &mut u64) -> u64 {
+    *x = x.wrapping_add(SPLITMIX64_GAMMA);
+    let mut z = *x;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// 64-bit FNV-1a, a stable hash for stream labels.
+fn fnv1a64(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &b in bytes {
+        hash ^= u64::from(b);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Independent random streams for the parts of a game, e.g., "ball_start" or one per enemy, so that an intervention adding a random draw in one part leaves the others' draws alone.
+/// Each stream is forked from `root` by name (see Gen::fork) the first time it is used, and saved with the state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "StreamsJson")]
+pub struct Streams {
+    root: Gen,
+    streams: BTreeMap<String, Gen>,
+}
+
+impl Streams {
+    pub fn new(root: Gen) -> Streams {
+        Streams {
+            root,
+            streams: BTreeMap::new(),
+        }
+    }
+    /// The stream called `name`.
+    pub fn stream(&mut self, name: &str) -> &mut Gen {
+        let root = &self.root;
+        self.streams
+            .entry(name.to_owned())
+            .or_insert_with(|| root.fork(name))
+    }
+    /// One of many streams called `name`, e.g., one per enemy; saved as "name/index".
+    pub fn stream_indexed(&mut self, name: &str, index: u64) -> &mut Gen {
+        let root = &self.root;
+        self.streams
+            .entry(format!("{}/{}", name, index))
+            .or_insert_with(|| root.fork_indexed(name, index))
+    }
+}
+
+/// States saved before Streams existed hold a single Gen, which becomes the root.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StreamsJson {
+    Streams {
+        root: Gen,
+        streams: BTreeMap<String, Gen>,
+    },
+    Gen(Gen),
+}
+
+impl From<StreamsJson> for Streams {
+    fn from(json: StreamsJson) -> Streams {
+        match json {
+            StreamsJson::Streams { root, streams } => Streams { root, streams },
+            StreamsJson::Gen(root) => Streams::new(root),
+        }
+    }
 }
 
 impl RngCore for Gen {
@@ -61,3 +170,79 @@ impl RngCore for Gen {
         Ok(self.fill_bytes(dest))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeding() {
+        // Reference values, so that seeds and forks stay reproducible across versions.
+        assert_eq!(splitmix64(&mut 0), 0xe220a8397b1dcdaf);
+        assert_eq!(fnv1a64(b"a"), 0xaf63dc4c8601ec8c);
+
+        let mut a = Gen::new_from_seed_u64(1);
+        let mut b = Gen::new_from_seed_u64(2);
+        let mut c = Gen::new_from_seed_u128(1 | (1 << 64));
+        let (a, b, c) = (a._next_u64(), b._next_u64(), c._next_u64());
+        assert!(a != b && a != c && b != c);
+        // Unlike new_from_seed, nearby seeds share no low-order structure.
+        assert!((a ^ b).count_ones() > 8);
+
+        let mut zero = Gen::new_from_seed_u128(0);
+        assert!(zero._next_u64() != 0 || zero._next_u64() != 0);
+
+        let mut reset = Gen::new_from_seed(5);
+        reset.reset_seed_u64(1);
+        assert_eq!(reset._next_u64(), a);
+    }
+
+    #[test]
+    fn test_fork() {
+        let mut parent = Gen::new_from_seed_u64(42);
+        let mut enemies = parent.fork("enemies");
+        let mut again = parent.fork("enemies");
+        let mut ball = parent.fork("ball");
+        let mut second = parent.fork_indexed("enemies", 1);
+        let first = enemies._next_u64();
+        assert_eq!(first, again._next_u64());
+        assert!(first != ball._next_u64());
+        assert!(first != second._next_u64());
+        assert_eq!(
+            parent.fork("enemies").state,
+            parent.fork_indexed("enemies", 0).state
+        );
+
+        // Forking does not advance the parent, but the children follow it.
+        let before = parent.clone()._next_u64();
+        assert_eq!(parent._next_u64(), before);
+        assert!(parent.fork("enemies")._next_u64() != first);
+    }
+
+    #[test]
+    fn test_streams() {
+        let mut streams = Streams::new(Gen::new_from_seed_u64(7));
+        let ball = streams.stream("ball")._next_u64();
+        // Drawing from other streams does not change what the next one returns.
+        let mut other = streams.clone();
+        other.stream("enemy")._next_u64();
+        other.stream_indexed("enemy", 3)._next_u64();
+        assert_eq!(
+            streams.stream("ball")._next_u64(),
+            other.stream("ball")._next_u64()
+        );
+        assert_eq!(ball, Gen::new_from_seed_u64(7).fork("ball")._next_u64());
+
+        let json = serde_json::to_string(&other).unwrap();
+        let mut loaded: Streams = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            loaded.stream_indexed("enemy", 3)._next_u64(),
+            other.stream_indexed("enemy", 3)._next_u64()
+        );
+
+        // A bare Gen, as states used to save, becomes the root.
+        let gen = Gen::new_from_seed(1);
+        let mut old: Streams = serde_json::from_str(&serde_json::to_string(&gen).unwrap()).unwrap();
+        assert_eq!(old.stream("ball").state, gen.fork("ball").state);
+    }
+}
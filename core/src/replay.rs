@@ -0,0 +1,196 @@
+//! Record an episode as a config, a starting state and the inputs applied to it, so that it can be re-simulated exactly later.
+use super::{Input, LoadError, Simulation, State};
+use serde_json;
+use std::error::Error;
+use std::fmt;
+
+/// The score and lives expected after a given number of frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// How many inputs have been applied when this checkpoint is taken.
+    pub frame: usize,
+    pub score: i32,
+    pub lives: i32,
+}
+
+impl Checkpoint {
+    fn of(frame: usize, state: &State) -> Checkpoint {
+        Checkpoint {
+            frame,
+            score: state.score(),
+            lives: state.lives(),
+        }
+    }
+}
+
+/// A recorded episode; works for any game through the Simulation and State traits.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    /// The Simulation config, as from Simulation::to_json.
+    pub config: serde_json::Value,
+    /// The state the episode started from, as from State::to_json.
+    pub start_state: serde_json::Value,
+    /// One Input per frame, packed with Input::to_bits.
+    pub inputs: Vec<u8>,
+    /// The analog paddle position of each frame's Input, which to_bits leaves out.
+    /// Empty if no frame used the paddle, so that button-only replays stay small.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paddle: Vec<Option<f32>>,
+    /// Sorted by frame; replaying checks each of these.
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+/// This enum defines failure conditions for re-simulating a Replay.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum ReplayError {
+    /// The config or a state in the replay could not be loaded.
+    Load(LoadError),
+    /// A recorded input byte is not a valid Input.
+    BadInput { frame: usize, bits: u8 },
+    /// The re-simulated game disagreed with a checkpoint.
+    Diverged {
+        expected: Checkpoint,
+        actual: Checkpoint,
+    },
+}
+
+impl ReplayError {
+    /// A stable integer for each kind of error, for use across the C API; load errors keep LoadError::code.
+    pub fn code(&self) -> i32 {
+        match self {
+            ReplayError::Load(e) => e.code(),
+            ReplayError::BadInput { .. } => 4,
+            ReplayError::Diverged { .. } => 5,
+        }
+    }
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Load(e) => write!(f, "{}", e),
+            ReplayError::BadInput { frame, bits } => {
+                write!(f, "Bad input at frame {}: {:#b}", frame, bits)
+            }
+            ReplayError::Diverged { expected, actual } => write!(
+                f,
+                "Replay diverged at frame {}: expected score={} lives={}, found score={} lives={}",
+                expected.frame, expected.score, expected.lives, actual.score, actual.lives
+            ),
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+impl From<LoadError> for ReplayError {
+    fn from(e: LoadError) -> ReplayError {
+        ReplayError::Load(e)
+    }
+}
+
+impl Replay {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Replay should be JSON-serializable!")
+    }
+
+    pub fn from_json(json_str: &str) -> Result<Replay, LoadError> {
+        Ok(serde_json::from_str(json_str)?)
+    }
+
+    /// The number of frames in this episode.
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    /// Re-simulate the episode, checking every checkpoint; returns the final state.
+    /// Any Simulation of the same game will do, since the recorded config replaces it.
+    pub fn play(&self, simulation: &Simulation) -> Result<Box<State>, ReplayError> {
+        let simulation = simulation.from_json(&self.config.to_string())?;
+        let mut state = simulation.new_state_from_json(&self.start_state.to_string())?;
+
+        let mut checkpoints = self.checkpoints.iter().peekable();
+        for frame in 0..=self.inputs.len() {
+            loop {
+                let expected = match checkpoints.peek() {
+                    Some(&&expected) if expected.frame == frame => expected,
+                    _ => break,
+                };
+                let actual = Checkpoint::of(frame, state.as_ref());
+                if expected != actual {
+                    return Err(ReplayError::Diverged { expected, actual });
+                }
+                checkpoints.next();
+            }
+            if let Some(&bits) = self.inputs.get(frame) {
+                let mut input =
+                    Input::from_bits(bits).ok_or(ReplayError::BadInput { frame, bits })?;
+                input.paddle = self.paddle.get(frame).cloned().unwrap_or(None);
+                state.update_mut(input);
+            }
+        }
+        Ok(state)
+    }
+}
+
+/// Builds a Replay while a game is played: step the state through the recorder rather than calling update_mut directly.
+pub struct ReplayRecorder {
+    replay: Replay,
+    /// Take a checkpoint every this many frames; zero means only at the start and end.
+    checkpoint_every: usize,
+    /// The score and lives after the most recent frame, used for the final checkpoint.
+    latest: Checkpoint,
+}
+
+impl ReplayRecorder {
+    /// Start recording from this state of this simulation.
+    pub fn new(simulation: &Simulation, state: &State, checkpoint_every: usize) -> ReplayRecorder {
+        let start = Checkpoint::of(0, state);
+        let parse = |json: String| -> serde_json::Value {
+            serde_json::from_str(&json).expect("to_json should produce valid JSON!")
+        };
+        ReplayRecorder {
+            replay: Replay {
+                config: parse(simulation.to_json()),
+                start_state: parse(state.to_json()),
+                inputs: Vec::new(),
+                paddle: Vec::new(),
+                checkpoints: vec![start],
+            },
+            checkpoint_every,
+            latest: start,
+        }
+    }
+
+    /// Apply an input to the state and record it.
+    // usize::is_multiple_of would need Rust 1.87.
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn step(&mut self, state: &mut State, buttons: Input) {
+        state.update_mut(buttons);
+        self.replay.inputs.push(buttons.to_bits());
+        if buttons.paddle.is_some() && self.replay.paddle.is_empty() {
+            // The first paddle input: every earlier frame had none.
+            self.replay.paddle = vec![None; self.replay.inputs.len() - 1];
+        }
+        if buttons.paddle.is_some() || !self.replay.paddle.is_empty() {
+            self.replay.paddle.push(buttons.paddle);
+        }
+        self.latest = Checkpoint::of(self.replay.inputs.len(), state);
+        if self.checkpoint_every > 0 && self.latest.frame % self.checkpoint_every == 0 {
+            self.replay.checkpoints.push(self.latest);
+        }
+    }
+
+    /// The episode so far, ending with a checkpoint at the most recent frame.
+    pub fn replay(&self) -> Replay {
+        let mut replay = self.replay.clone();
+        if replay.checkpoints.last() != Some(&self.latest) {
+            replay.checkpoints.push(self.latest);
+        }
+        replay
+    }
+}
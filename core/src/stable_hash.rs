@@ -0,0 +1,76 @@
+use serde::Serialize;
+use serde_json;
+use std::hash::Hasher;
+use std::io;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// A 64-bit FNV-1a hasher. Unlike std's DefaultHasher, its output is fixed by the algorithm, so hashes agree across runs, builds and machines.
+pub struct StableHasher {
+    hash: u64,
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher {
+            hash: FNV_OFFSET_BASIS,
+        }
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.hash ^= b as u64;
+            self.hash = self.hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+/// Lets serde_json stream straight into the hash without building a String.
+impl io::Write for StableHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Hasher::write(self, buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Hash the JSON serialization of a value; equal JSON means equal hashes.
+/// Anything with unordered collections (e.g., HashSet) will not hash stably, so game states use ordered ones.
+pub fn stable_hash<T: Serialize>(value: &T) -> u64 {
+    let mut hasher = StableHasher::default();
+    serde_json::to_writer(&mut hasher, value).expect("Should be no JSON Serialization Errors.");
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fnv1a_known_values() {
+        let mut hasher = StableHasher::default();
+        Hasher::write(&mut hasher, b"");
+        assert_eq!(0xcbf29ce484222325, hasher.finish());
+        Hasher::write(&mut hasher, b"a");
+        assert_eq!(0xaf63dc4c8601ec8c, hasher.finish());
+    }
+
+    #[test]
+    fn test_stable_hash_matches_json() {
+        let value = (1, "two", vec![3.5]);
+        let mut hasher = StableHasher::default();
+        Hasher::write(
+            &mut hasher,
+            serde_json::to_string(&value).unwrap().as_bytes(),
+        );
+        assert_eq!(hasher.finish(), stable_hash(&value));
+    }
+}
@@ -0,0 +1,357 @@
+use super::graphics::{Color, GrayscaleBuffer, ImageBuffer};
+use super::random;
+use super::{Input, LoadError, Simulation, State};
+use rand::Rng;
+use serde_json;
+
+/// How a StepWrapper turns one agent action into emulator frames; the defaults apply each action to exactly one frame.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StepWrapperConfig {
+    /// How many frames each action is applied for (ALE's frameskip); must be at least 1.
+    pub frameskip: u32,
+    /// Render the elementwise max of the last two frames, as in DQN preprocessing, to undo Atari sprite flicker.
+    pub max_pool: bool,
+    /// On each frame, the probability that the previous input is repeated instead of the requested one (ALE's sticky actions).
+    pub repeat_action_probability: f64,
+}
+
+impl Default for StepWrapperConfig {
+    fn default() -> Self {
+        StepWrapperConfig {
+            frameskip: 1,
+            max_pool: false,
+            repeat_action_probability: 0.0,
+        }
+    }
+}
+
+impl StepWrapperConfig {
+    /// The settings of the ALE v5 environments: frameskip 4, max-pooling, and 25% sticky actions.
+    pub fn ale() -> StepWrapperConfig {
+        StepWrapperConfig {
+            frameskip: 4,
+            max_pool: true,
+            repeat_action_probability: 0.25,
+        }
+    }
+
+    /// Returns a description of the first problem with this config, if any.
+    // RangeInclusive::contains would need Rust 1.35.
+    #[allow(clippy::manual_range_contains)]
+    pub fn check(&self) -> Result<(), String> {
+        if self.frameskip < 1 {
+            return Err("frameskip must be at least 1.".to_owned());
+        }
+        let p = self.repeat_action_probability;
+        if p.is_nan() || p < 0.0 || p > 1.0 {
+            return Err(format!(
+                "repeat_action_probability should be in [0, 1], not {}.",
+                p
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Wraps any State so that each step applies an action for several frames, with sticky actions and max-pooled rendering.
+/// The random choices come from the wrapper's own Gen, so a wrapper (and the state inside) round-trips through to_json deterministically.
+pub struct StepWrapper {
+    pub config: StepWrapperConfig,
+    pub state: Box<State>,
+    game_size: (i32, i32),
+    rand: random::Gen,
+    /// The input actually applied on the most recent frame; this is what sticks.
+    last_input: Input,
+    /// The RGBA frame just before the most recent one, kept only when max-pooling.
+    prev_frame: Option<Vec<u8>>,
+}
+
+/// Everything a StepWrapper needs to save, with the wrapped state as nested JSON.
+#[derive(Serialize, Deserialize)]
+struct StepWrapperJson {
+    config: StepWrapperConfig,
+    game_size: (i32, i32),
+    rand: random::Gen,
+    last_input: Input,
+    prev_frame: Option<Vec<u8>>,
+    state: serde_json::Value,
+}
+
+impl StepWrapper {
+    /// Wrap a state of a game whose frames are game_size pixels; the seed drives sticky actions only.
+    pub fn new(
+        state: Box<State>,
+        game_size: (i32, i32),
+        config: StepWrapperConfig,
+        seed: u32,
+    ) -> StepWrapper {
+        StepWrapper {
+            config,
+            state,
+            game_size,
+            rand: random::Gen::new_from_seed(seed),
+            last_input: Input::default(),
+            prev_frame: None,
+        }
+    }
+
+    /// Start a new game of this simulation inside a wrapper.
+    pub fn new_game(
+        simulation: &mut Simulation,
+        config: StepWrapperConfig,
+        seed: u32,
+    ) -> StepWrapper {
+        StepWrapper::new(simulation.new_game(), simulation.game_size(), config, seed)
+    }
+
+    pub fn lives(&self) -> i32 {
+        self.state.lives()
+    }
+
+    pub fn score(&self) -> i32 {
+        self.state.score()
+    }
+
+    /// Apply this input for up to config.frameskip frames, stopping early at game over; returns the change in score.
+    pub fn step(&mut self, buttons: Input) -> i32 {
+        let start_score = self.state.score();
+        // A frame from an earlier step must never be pooled with this one, e.g., if the game ends early.
+        self.prev_frame = None;
+        for frame in 0..self.config.frameskip {
+            if self.state.lives() < 0 {
+                break;
+            }
+            let p = self.config.repeat_action_probability;
+            if p <= 0.0 || !self.rand.gen_bool(p) {
+                self.last_input = buttons;
+            }
+            // Only the frame before the last one is needed for pooling.
+            if self.config.max_pool && frame + 1 == self.config.frameskip {
+                self.prev_frame = Some(self.render_unpooled().data);
+            }
+            self.state.update_mut(self.last_input);
+        }
+        self.state.score() - start_score
+    }
+
+    fn render_unpooled(&self) -> ImageBuffer {
+        let (w, h) = self.game_size;
+        let mut img = ImageBuffer::alloc(w, h);
+        img.render(&self.state.draw());
+        img
+    }
+
+    /// Render the current frame as RGBA, pooled with the previous frame if config.max_pool is set.
+    pub fn render(&self) -> ImageBuffer {
+        let mut img = self.render_unpooled();
+        if let (true, Some(prev)) = (self.config.max_pool, self.prev_frame.as_ref()) {
+            for (px, &old) in img.data.iter_mut().zip(prev.iter()) {
+                *px = (*px).max(old);
+            }
+        }
+        img
+    }
+
+    /// Like render, but the (possibly pooled) colors are then converted to grayscale.
+    pub fn render_grayscale(&self) -> GrayscaleBuffer {
+        let img = self.render();
+        let mut gray = GrayscaleBuffer::alloc(img.width, img.height);
+        for (out, px) in gray.data.iter_mut().zip(img.data.chunks_exact(4)) {
+            *out = Color::rgb(px[0], px[1], px[2]).grayscale_byte();
+        }
+        gray
+    }
+
+    pub fn to_json(&self) -> String {
+        let state: serde_json::Value = serde_json::from_str(&self.state.to_json())
+            .expect("State::to_json should produce valid JSON!");
+        let saved = StepWrapperJson {
+            config: self.config,
+            game_size: self.game_size,
+            rand: self.rand.clone(),
+            last_input: self.last_input,
+            prev_frame: self.prev_frame.clone(),
+            state,
+        };
+        serde_json::to_string(&saved).expect("StepWrapper should be JSON-serializable!")
+    }
+
+    /// Load a wrapper saved by to_json; the nested state is loaded by this simulation.
+    pub fn from_json(simulation: &Simulation, json_str: &str) -> Result<StepWrapper, LoadError> {
+        let saved: StepWrapperJson = serde_json::from_str(json_str)?;
+        saved.config.check().map_err(LoadError::InvalidConfig)?;
+        let state = simulation.new_state_from_json(&saved.state.to_string())?;
+        Ok(StepWrapper {
+            config: saved.config,
+            state,
+            game_size: saved.game_size,
+            rand: saved.rand,
+            last_input: saved.last_input,
+            prev_frame: saved.prev_frame,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graphics::Drawable;
+    use Event;
+    use Object;
+    use QueryError;
+    use StepResult;
+
+    /// Scores a point per frame and draws one white pixel that moves right each frame, until the game ends at last_frame.
+    #[derive(Clone)]
+    struct Counter {
+        frame: i32,
+        last_frame: i32,
+    }
+    impl State for Counter {
+        fn lives(&self) -> i32 {
+            if self.frame < self.last_frame {
+                1
+            } else {
+                -1
+            }
+        }
+        fn score(&self) -> i32 {
+            self.frame
+        }
+        fn update_mut(&mut self, _buttons: Input) {
+            self.frame += 1;
+        }
+        fn step(&mut self, buttons: Input) -> StepResult {
+            let (score, lives) = (self.score(), self.lives());
+            self.update_mut(buttons);
+            StepResult::diff(score, lives, self)
+        }
+        fn drain_events(&mut self) -> Vec<Event> {
+            Vec::new()
+        }
+        fn objects(&self) -> Vec<Object> {
+            vec![Object::new("dot", self.frame, 0, 1, 1)]
+        }
+        fn draw(&self) -> Vec<Drawable> {
+            vec![
+                Drawable::Clear(Color::black()),
+                Drawable::rect(Color::white(), self.frame, 0, 1, 1),
+            ]
+        }
+        fn to_text(&self) -> String {
+            format!("{}", self.frame)
+        }
+        fn to_json(&self) -> String {
+            format!("{}", self.frame)
+        }
+        fn query_json(&self, _: &str, _: &serde_json::Value) -> Result<String, QueryError> {
+            Err(QueryError::NoSuchQuery)
+        }
+        fn clone_box(&self) -> Box<State> {
+            Box::new(self.clone())
+        }
+        fn state_hash(&self) -> u64 {
+            ::stable_hash(&self.frame)
+        }
+    }
+
+    #[test]
+    fn test_frameskip_and_max_pool() {
+        let config = StepWrapperConfig {
+            frameskip: 3,
+            max_pool: true,
+            repeat_action_probability: 0.0,
+        };
+        let mut wrapper = StepWrapper::new(
+            Box::new(Counter {
+                frame: 0,
+                last_frame: 100,
+            }),
+            (8, 1),
+            config,
+            7,
+        );
+        assert_eq!(wrapper.step(Input::default()), 3);
+        // Frames 2 and 3 are both visible; frame 1 was skipped entirely.
+        let lit: Vec<u8> = wrapper.render_grayscale().data;
+        assert_eq!(lit, vec![0, 0, 255, 255, 0, 0, 0, 0]);
+
+        wrapper.config.max_pool = false;
+        assert_eq!(
+            wrapper.render_grayscale().data,
+            vec![0, 0, 0, 255, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_max_pool_after_game_over() {
+        let config = StepWrapperConfig {
+            frameskip: 3,
+            max_pool: true,
+            repeat_action_probability: 0.0,
+        };
+        let counter = Counter {
+            frame: 0,
+            last_frame: 4,
+        };
+        let mut wrapper = StepWrapper::new(Box::new(counter), (8, 1), config, 7);
+        assert_eq!(wrapper.step(Input::default()), 3);
+        // The game ends after one more frame; frame 2 from the first step must not show up.
+        assert_eq!(wrapper.step(Input::default()), 1);
+        assert_eq!(
+            wrapper.render_grayscale().data,
+            vec![0, 0, 0, 0, 255, 0, 0, 0]
+        );
+        assert_eq!(wrapper.step(Input::default()), 0);
+        assert_eq!(
+            wrapper.render_grayscale().data,
+            vec![0, 0, 0, 0, 255, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_sticky_actions_are_seeded() {
+        let config = StepWrapperConfig {
+            frameskip: 1,
+            max_pool: false,
+            repeat_action_probability: 0.5,
+        };
+        let run = |seed| {
+            let mut wrapper = StepWrapper::new(
+                Box::new(Counter {
+                    frame: 0,
+                    last_frame: 100,
+                }),
+                (8, 1),
+                config,
+                seed,
+            );
+            let mut applied = Vec::new();
+            for i in 0..64 {
+                let buttons = Input {
+                    left: i % 2 == 0,
+                    ..Input::default()
+                };
+                wrapper.step(buttons);
+                applied.push(wrapper.last_input.left);
+            }
+            applied
+        };
+        assert_eq!(run(13), run(13));
+        // With p=0.5 over 64 alternating inputs, some input must have stuck.
+        let expected: Vec<bool> = (0..64).map(|i| i % 2 == 0).collect();
+        assert_ne!(run(13), expected);
+    }
+
+    #[test]
+    fn test_check_config() {
+        assert!(StepWrapperConfig::default().check().is_ok());
+        assert!(StepWrapperConfig::ale().check().is_ok());
+        let mut config = StepWrapperConfig::ale();
+        config.frameskip = 0;
+        assert!(config.check().is_err());
+        config.frameskip = 4;
+        config.repeat_action_probability = 1.5;
+        assert!(config.check().is_err());
+    }
+}
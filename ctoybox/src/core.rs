@@ -1,3 +1,4 @@
+use super::set_last_error;
 use super::WrapSimulator;
 use super::WrapState;
 use libc::{c_char, c_void};
@@ -5,20 +6,32 @@ use serde_json;
 use std::boxed::Box;
 use std::ffi::{CStr, CString};
 use std::mem;
+use std::ptr;
+use std::slice;
 use toybox;
-use toybox_core::graphics::{GrayscaleBuffer, ImageBuffer};
-use toybox_core::{AleAction, Input, State};
+use toybox_core::graphics::{
+    Crop, GrayscaleBuffer, ImageBuffer, MaskBuffer, PaletteBuffer, Resample, SvgOptions,
+    SvgRenderer,
+};
+use toybox_core::{encode_objects, object_row_width, AleAction, Input, LoadError, State};
 
 #[no_mangle]
 pub extern "C" fn free_str(originally_from_rust: *mut c_char) {
     let _will_drop: CString = unsafe { CString::from_raw(originally_from_rust) };
 }
 
+/// Returns NULL if there is no such game; see last_error_message.
 #[no_mangle]
 pub extern "C" fn simulator_alloc(name: *const c_char) -> *mut WrapSimulator {
     let name: &CStr = unsafe { CStr::from_ptr(name) };
     let name: &str = name.to_str().expect("bad utf-8!");
-    let simulator = toybox::get_simulation_by_name(name).unwrap();
+    let simulator = match toybox::get_simulation_by_name(name) {
+        Ok(simulator) => simulator,
+        Err(e) => {
+            set_last_error(e);
+            return ptr::null_mut();
+        }
+    };
     // The boxing stuff ensures the pointer remains allocated after
     // we leave this scope.
     let simulator = Box::new(WrapSimulator { simulator });
@@ -45,6 +58,16 @@ pub extern "C" fn simulator_seed(ptr: *mut WrapSimulator, seed: u32) {
     simulator.reset_seed(seed);
 }
 
+// Reset the simulator RNG to a 64-bit seed, expanded so that nearby seeds are unrelated.
+#[no_mangle]
+pub extern "C" fn simulator_seed_u64(ptr: *mut WrapSimulator, seed: u64) {
+    let &mut WrapSimulator { ref mut simulator } = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    simulator.reset_seed_u64(seed);
+}
+
 #[no_mangle]
 pub extern "C" fn simulator_to_json(ptr: *mut WrapSimulator) -> *const c_char {
     let &mut WrapSimulator { ref mut simulator } = unsafe {
@@ -87,6 +110,67 @@ pub extern "C" fn simulator_actions(ptr: *mut WrapSimulator) -> *const c_char {
     CString::into_raw(cjson)
 }
 
+/// Returns this game's ActionSpace as JSON: the name, Input and no-op situations of each discrete action, and whether it reads an analog paddle.
+#[no_mangle]
+pub extern "C" fn simulator_action_space(ptr: *mut WrapSimulator) -> *const c_char {
+    let &mut WrapSimulator { ref mut simulator } = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    let space = serde_json::to_string(&simulator.action_space())
+        .expect("ActionSpace to JSON should be OK.");
+    let cjson: CString = CString::new(space).expect("Conversion to CString should succeed!");
+    CString::into_raw(cjson)
+}
+
+#[no_mangle]
+pub extern "C" fn simulator_validate(ptr: *mut WrapSimulator) -> *const c_char {
+    let &mut WrapSimulator { ref mut simulator } = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    let problems =
+        serde_json::to_string(&simulator.validate()).expect("Vector to JSON should be OK.");
+    let cjson: CString = CString::new(problems).expect("Conversion to CString should succeed!");
+    CString::into_raw(cjson)
+}
+
+/// Returns a JSON list of the object kinds this game reports, in the order used by state_objects_tensor.
+#[no_mangle]
+pub extern "C" fn simulator_object_kinds(ptr: *mut WrapSimulator) -> *const c_char {
+    let &mut WrapSimulator { ref mut simulator } = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    let kinds =
+        serde_json::to_string(&simulator.object_kinds()).expect("Vector to JSON should be OK.");
+    let cjson: CString = CString::new(kinds).expect("Conversion to CString should succeed!");
+    CString::into_raw(cjson)
+}
+
+/// Returns a JSON list of every color this game draws; render_current_frame_palette writes indices into it.
+#[no_mangle]
+pub extern "C" fn simulator_palette(ptr: *mut WrapSimulator) -> *const c_char {
+    let &mut WrapSimulator { ref mut simulator } = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    let palette =
+        serde_json::to_string(&simulator.palette()).expect("Vector to JSON should be OK.");
+    let cjson: CString = CString::new(palette).expect("Conversion to CString should succeed!");
+    CString::into_raw(cjson)
+}
+
+/// How many floats state_objects_tensor writes per object for this game.
+#[no_mangle]
+pub extern "C" fn simulator_object_row_width(ptr: *mut WrapSimulator) -> usize {
+    let &mut WrapSimulator { ref mut simulator } = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    object_row_width(simulator.object_kinds().len())
+}
+
 // STATE ALLOC + FREE
 #[no_mangle]
 pub extern "C" fn state_alloc(ptr: *mut WrapSimulator) -> *mut WrapState {
@@ -99,6 +183,18 @@ pub extern "C" fn state_alloc(ptr: *mut WrapSimulator) -> *mut WrapState {
     Box::into_raw(boxed_wrapped_state)
 }
 
+/// Copy a state; the copy must be released with state_free like any other.
+#[no_mangle]
+pub extern "C" fn state_clone(ptr: *mut WrapState) -> *mut WrapState {
+    let &mut WrapState { ref mut state } = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    Box::into_raw(Box::new(WrapState {
+        state: state.clone_box(),
+    }))
+}
+
 #[no_mangle]
 pub extern "C" fn state_free(ptr: *mut WrapState) {
     if ptr.is_null() {
@@ -200,6 +296,157 @@ pub extern "C" fn render_current_frame(
     mem::forget(dat)
 }
 
+/// Writes one palette index per pixel, into simulator_palette.
+/// Returns NULL if every color was already in it; otherwise, returns the grown palette as a JSON list (simulator_palette, then the missing colors), to be freed with free_str.
+#[no_mangle]
+pub extern "C" fn render_current_frame_palette(
+    numpy_pixels: *mut u8,
+    numpy_pixels_len: usize,
+    sim_ptr: *mut WrapSimulator,
+    state_ptr: *mut WrapState,
+) -> *const c_char {
+    let &mut WrapSimulator { ref mut simulator } = unsafe {
+        assert!(!sim_ptr.is_null());
+        &mut *sim_ptr
+    };
+    let &mut WrapState { ref mut state } = unsafe {
+        assert!(!state_ptr.is_null());
+        &mut *state_ptr
+    };
+    let pixels = unsafe {
+        assert!(!numpy_pixels.is_null());
+        slice::from_raw_parts_mut(numpy_pixels, numpy_pixels_len)
+    };
+    let (w, h) = simulator.game_size();
+    let palette = simulator.palette();
+    let known = palette.len();
+    let mut img = PaletteBuffer::alloc(w, h, palette);
+    img.render(&state.draw());
+    assert_eq!(numpy_pixels_len, img.data.len());
+    pixels.copy_from_slice(&img.data);
+    if img.palette.len() == known {
+        return ptr::null();
+    }
+
+    let palette = serde_json::to_string(&img.palette).expect("Vector to JSON should be OK.");
+    let cjson: CString = CString::new(palette).expect("Conversion to CString should succeed!");
+    CString::into_raw(cjson)
+}
+
+/// Like render_current_frame, but crops the frame and resizes it to out_width by out_height first.
+/// A crop_w or crop_h of zero or less means the whole frame; method is 0 for nearest, 1 for bilinear and 2 for area resampling.
+/// Returns false (and leaves the buffer alone) for an unknown method.
+#[no_mangle]
+pub extern "C" fn render_current_frame_resized(
+    numpy_pixels: *mut u8,
+    numpy_pixels_len: usize,
+    grayscale: bool,
+    sim_ptr: *mut WrapSimulator,
+    state_ptr: *mut WrapState,
+    crop_x: i32,
+    crop_y: i32,
+    crop_w: i32,
+    crop_h: i32,
+    out_width: i32,
+    out_height: i32,
+    method: i32,
+) -> bool {
+    let &mut WrapSimulator { ref mut simulator } = unsafe {
+        assert!(!sim_ptr.is_null());
+        &mut *sim_ptr
+    };
+    let &mut WrapState { ref mut state } = unsafe {
+        assert!(!state_ptr.is_null());
+        &mut *state_ptr
+    };
+    let pixels = unsafe {
+        assert!(!numpy_pixels.is_null());
+        slice::from_raw_parts_mut(numpy_pixels, numpy_pixels_len)
+    };
+    let method = match Resample::from_int(method) {
+        Some(m) => m,
+        None => return false,
+    };
+    let (w, h) = simulator.game_size();
+    let crop = if crop_w > 0 && crop_h > 0 {
+        Crop::new(crop_x, crop_y, crop_w, crop_h)
+    } else {
+        Crop::full(w, h)
+    };
+
+    let imgdata = if grayscale {
+        let mut img = GrayscaleBuffer::alloc(w, h);
+        img.render(&state.draw());
+        img.resized(crop, out_width, out_height, method).data
+    } else {
+        let mut img = ImageBuffer::alloc(w, h);
+        img.render(&state.draw());
+        img.resized(crop, out_width, out_height, method).data
+    };
+    assert_eq!(numpy_pixels_len, imgdata.len());
+    pixels.copy_from_slice(&imgdata);
+    true
+}
+
+/// Fills one class id and one instance id per pixel; see toybox_core::graphics::MaskBuffer and simulator_object_kinds.
+#[no_mangle]
+pub extern "C" fn render_mask(
+    class_ids: *mut u8,
+    instance_ids: *mut i32,
+    pixels_len: usize,
+    sim_ptr: *mut WrapSimulator,
+    state_ptr: *mut WrapState,
+) {
+    let &mut WrapSimulator { ref mut simulator } = unsafe {
+        assert!(!sim_ptr.is_null());
+        &mut *sim_ptr
+    };
+    let &mut WrapState { ref mut state } = unsafe {
+        assert!(!state_ptr.is_null());
+        &mut *state_ptr
+    };
+    let (class_ids, instance_ids) = unsafe {
+        assert!(!class_ids.is_null() && !instance_ids.is_null());
+        (
+            slice::from_raw_parts_mut(class_ids, pixels_len),
+            slice::from_raw_parts_mut(instance_ids, pixels_len),
+        )
+    };
+    let (w, h) = simulator.game_size();
+    let mut mask = MaskBuffer::alloc(w, h);
+    mask.render(&state.draw());
+    assert_eq!(pixels_len, mask.class_ids.len());
+    class_ids.copy_from_slice(&mask.class_ids);
+    instance_ids.copy_from_slice(&mask.instance_ids);
+}
+
+/// Returns an SVG document of the current frame; free it with free_str.
+#[no_mangle]
+pub extern "C" fn render_svg(
+    sim_ptr: *mut WrapSimulator,
+    state_ptr: *mut WrapState,
+    scale: f64,
+    annotate: bool,
+) -> *mut c_void {
+    let &mut WrapSimulator { ref mut simulator } = unsafe {
+        assert!(!sim_ptr.is_null());
+        &mut *sim_ptr
+    };
+    let &mut WrapState { ref mut state } = unsafe {
+        assert!(!state_ptr.is_null());
+        &mut *state_ptr
+    };
+    let (w, h) = simulator.game_size();
+    let options = SvgOptions {
+        scale,
+        annotate,
+        kinds: simulator.object_kinds(),
+    };
+    let svg = SvgRenderer::new(w, h, options).render(&state.draw());
+    let csvg: CString = CString::new(svg).expect("Conversion to CString should succeed!");
+    CString::into_raw(csvg) as *mut c_void
+}
+
 #[no_mangle]
 pub extern "C" fn state_apply_ale_action(state_ptr: *mut WrapState, input: i32) -> bool {
     let &mut WrapState { ref mut state } = unsafe {
@@ -214,6 +461,23 @@ pub extern "C" fn state_apply_ale_action(state_ptr: *mut WrapState, input: i32)
     }
 }
 
+/// Like state_apply_ale_action, but returns a JSON StepResult describing the step, or NULL for an illegal action.
+#[no_mangle]
+pub extern "C" fn state_step_ale_action(state_ptr: *mut WrapState, input: i32) -> *const c_char {
+    let &mut WrapState { ref mut state } = unsafe {
+        assert!(!state_ptr.is_null());
+        &mut *state_ptr
+    };
+    if let Some(input) = AleAction::from_int(input).map(|a| a.to_input()) {
+        let result = state.step(input);
+        let json = serde_json::to_string(&result).expect("StepResult to JSON should be OK.");
+        let cjson: CString = CString::new(json).expect("Conversion to CString should succeed!");
+        CString::into_raw(cjson)
+    } else {
+        ptr::null()
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn state_apply_action(state_ptr: *mut WrapState, input_ptr: *const c_char) {
     let &mut WrapState { ref mut state } = unsafe {
@@ -249,6 +513,65 @@ pub extern "C" fn state_score(state_ptr: *mut WrapState) -> i32 {
     state.score()
 }
 
+#[no_mangle]
+pub extern "C" fn state_hash(state_ptr: *mut WrapState) -> u64 {
+    let &mut WrapState { ref mut state } = unsafe {
+        assert!(!state_ptr.is_null());
+        &mut *state_ptr
+    };
+    state.state_hash()
+}
+
+/// Returns a JSON list of the events logged since the last call; free it with free_str.
+#[no_mangle]
+pub extern "C" fn state_drain_events(state_ptr: *mut WrapState) -> *const c_char {
+    let &mut WrapState { ref mut state } = unsafe {
+        assert!(!state_ptr.is_null());
+        &mut *state_ptr
+    };
+    let json = serde_json::to_string(&state.drain_events()).expect("Events to JSON should be OK.");
+    let cjson: CString = CString::new(json).expect("Conversion to CString should succeed!");
+    CString::into_raw(cjson)
+}
+
+/// Returns a JSON list of the objects in this frame; free it with free_str.
+#[no_mangle]
+pub extern "C" fn state_objects_json(state_ptr: *mut WrapState) -> *const c_char {
+    let &mut WrapState { ref mut state } = unsafe {
+        assert!(!state_ptr.is_null());
+        &mut *state_ptr
+    };
+    let json = serde_json::to_string(&state.objects()).expect("Objects to JSON should be OK.");
+    let cjson: CString = CString::new(json).expect("Conversion to CString should succeed!");
+    CString::into_raw(cjson)
+}
+
+/// Writes the objects in this frame as a (max_objects, simulator_object_row_width) row-major tensor; see toybox_core::encode_objects.
+#[no_mangle]
+pub extern "C" fn state_objects_tensor(
+    sim_ptr: *mut WrapSimulator,
+    state_ptr: *mut WrapState,
+    max_objects: usize,
+    output: *mut f32,
+    output_len: usize,
+) {
+    let &mut WrapSimulator { ref mut simulator } = unsafe {
+        assert!(!sim_ptr.is_null());
+        &mut *sim_ptr
+    };
+    let &mut WrapState { ref mut state } = unsafe {
+        assert!(!state_ptr.is_null());
+        &mut *state_ptr
+    };
+    let output = unsafe {
+        assert!(!output.is_null());
+        slice::from_raw_parts_mut(output, output_len)
+    };
+    let tensor = encode_objects(&state.objects(), &simulator.object_kinds(), max_objects);
+    assert_eq!(output_len, tensor.len());
+    output.copy_from_slice(&tensor);
+}
+
 #[no_mangle]
 pub extern "C" fn state_to_json(state_ptr: *mut WrapState) -> *mut c_void {
     let &mut WrapState { ref mut state } = unsafe {
@@ -261,44 +584,63 @@ pub extern "C" fn state_to_json(state_ptr: *mut WrapState) -> *mut c_void {
     CString::into_raw(cjson) as *mut c_void
 }
 
+/// A game-specific plain-text picture of the state; free it with free_str.
+#[no_mangle]
+pub extern "C" fn state_to_text(state_ptr: *mut WrapState) -> *mut c_void {
+    let &mut WrapState { ref mut state } = unsafe {
+        assert!(!state_ptr.is_null());
+        &mut *state_ptr
+    };
+
+    let text: String = state.to_text();
+    let ctext: CString = CString::new(text).expect("Conversion to CString should succeed!");
+    CString::into_raw(ctext) as *mut c_void
+}
+
+/// Returns NULL if the JSON cannot be loaded; see last_error_code and last_error_message.
 #[no_mangle]
 pub extern "C" fn state_from_json(
     ptr: *mut WrapSimulator,
     json_str: *const c_char,
 ) -> *mut WrapState {
     let json_str: &CStr = unsafe { CStr::from_ptr(json_str) };
-    let json_str: &str = json_str
-        .to_str()
-        .expect("Could not convert your string to UTF-8!");
     let &mut WrapSimulator { ref mut simulator } = unsafe {
         assert!(!ptr.is_null());
         &mut *ptr
     };
-    let state = simulator
-        .new_state_from_json(json_str)
-        .expect("Could not parse state JSON!");
-    let state = Box::new(WrapState { state });
-    Box::into_raw(state)
+    let loaded = json_str
+        .to_str()
+        .map_err(|e| LoadError::ParseError(format!("{}", e)))
+        .and_then(|json_str| simulator.new_state_from_json(json_str));
+    match loaded {
+        Ok(state) => Box::into_raw(Box::new(WrapState { state })),
+        Err(e) => {
+            set_last_error(e);
+            ptr::null_mut()
+        }
+    }
 }
 
+/// Returns NULL if the JSON cannot be loaded; see last_error_code and last_error_message.
 #[no_mangle]
 pub extern "C" fn simulator_from_json(
     ptr: *mut WrapSimulator,
     json_str: *const c_char,
 ) -> *mut WrapSimulator {
     let json_str: &CStr = unsafe { CStr::from_ptr(json_str) };
-    let json_str: &str = json_str
-        .to_str()
-        .expect("Could not convert your config string to UTF-8!");
-
     let &mut WrapSimulator { ref mut simulator } = unsafe {
         assert!(!ptr.is_null());
         &mut *ptr
     };
-    let new_sim = simulator
-        .from_json(json_str)
-        .expect("Could not parse some JSON!");
-
-    let out = Box::new(WrapSimulator { simulator: new_sim });
-    Box::into_raw(out)
+    let loaded = json_str
+        .to_str()
+        .map_err(|e| LoadError::ParseError(format!("{}", e)))
+        .and_then(|json_str| simulator.from_json(json_str));
+    match loaded {
+        Ok(new_sim) => Box::into_raw(Box::new(WrapSimulator { simulator: new_sim })),
+        Err(e) => {
+            set_last_error(e);
+            ptr::null_mut()
+        }
+    }
 }
@@ -0,0 +1,60 @@
+use libc::c_char;
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::fmt;
+use std::ptr;
+use toybox_core::replay::ReplayError;
+use toybox_core::LoadError;
+
+/// Errors that C callers can inspect with last_error_code and last_error_message.
+pub trait CodedError: fmt::Display {
+    /// A stable, non-zero integer for this kind of error.
+    fn error_code(&self) -> i32;
+}
+
+impl CodedError for LoadError {
+    fn error_code(&self) -> i32 {
+        self.code()
+    }
+}
+
+impl CodedError for ReplayError {
+    fn error_code(&self) -> i32 {
+        self.code()
+    }
+}
+
+thread_local! {
+    /// The code and message of the most recent error from a fallible C API call on this thread.
+    static LAST_ERROR: RefCell<Option<(i32, String)>> = RefCell::new(None);
+}
+
+/// Remember an error so that C callers can ask about it after a call returns NULL.
+pub fn set_last_error<E: CodedError>(err: E) {
+    let saved = (err.error_code(), format!("{}", err));
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(saved));
+}
+
+/// Returns the code of the most recent error on this thread (see LoadError::code and ReplayError::code), or zero if there is none.
+#[no_mangle]
+pub extern "C" fn last_error_code() -> i32 {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map(|e| e.0).unwrap_or(0))
+}
+
+/// Returns a description of the most recent error on this thread, or NULL if there is none. Release it with free_str.
+#[no_mangle]
+pub extern "C" fn last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| match *last.borrow() {
+        Some((_, ref msg)) => {
+            let msg = CString::new(msg.clone()).expect("Error messages should not contain NUL!");
+            CString::into_raw(msg)
+        }
+        None => ptr::null(),
+    })
+}
+
+/// Forget the most recent error on this thread.
+#[no_mangle]
+pub extern "C" fn clear_last_error() {
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+}
@@ -0,0 +1,127 @@
+use super::WrapFrameStack;
+use super::WrapSimulator;
+use super::WrapState;
+use std::slice;
+use toybox_core::graphics::{Crop, FrameStack, GrayscaleBuffer, ImageBuffer, Resample};
+
+/// Keep the last depth frames of width by height pixels; frames of any other size are area-resampled to fit when pushed.
+#[no_mangle]
+pub extern "C" fn frame_stack_alloc(
+    width: i32,
+    height: i32,
+    grayscale: bool,
+    depth: usize,
+) -> *mut WrapFrameStack {
+    let channels = if grayscale { 1 } else { 4 };
+    let stack = FrameStack::new(width, height, channels, depth);
+    Box::into_raw(Box::new(WrapFrameStack { stack }))
+}
+
+#[no_mangle]
+pub extern "C" fn frame_stack_free(ptr: *mut WrapFrameStack) {
+    if ptr.is_null() {
+        return;
+    }
+    unsafe {
+        Box::from_raw(ptr);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn frame_stack_clear(ptr: *mut WrapFrameStack) {
+    let &mut WrapFrameStack { ref mut stack } = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    stack.clear();
+}
+
+/// Render the current frame of a state (as render_current_frame would) and push it, replacing the oldest frame.
+#[no_mangle]
+pub extern "C" fn frame_stack_push_frame(
+    ptr: *mut WrapFrameStack,
+    sim_ptr: *mut WrapSimulator,
+    state_ptr: *mut WrapState,
+) {
+    let &mut WrapFrameStack { ref mut stack } = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    let &mut WrapSimulator { ref mut simulator } = unsafe {
+        assert!(!sim_ptr.is_null());
+        &mut *sim_ptr
+    };
+    let &mut WrapState { ref mut state } = unsafe {
+        assert!(!state_ptr.is_null());
+        &mut *state_ptr
+    };
+    let (w, h) = simulator.game_size();
+    let resize = w != stack.width || h != stack.height;
+    let (out_w, out_h) = (stack.width, stack.height);
+
+    if stack.channels == 1 {
+        let mut img = GrayscaleBuffer::alloc(w, h);
+        img.render(&state.draw());
+        if resize {
+            img = img.resized(Crop::full(w, h), out_w, out_h, Resample::Area);
+        }
+        stack.push_grayscale(&img);
+    } else {
+        let mut img = ImageBuffer::alloc(w, h);
+        img.render(&state.draw());
+        if resize {
+            img = img.resized(Crop::full(w, h), out_w, out_h, Resample::Area);
+        }
+        stack.push_image(&img);
+    }
+}
+
+/// Write every frame, oldest first, as (height, width, depth * channels) or, if channels_first, (depth * channels, height, width).
+#[no_mangle]
+pub extern "C" fn frame_stack_write(
+    ptr: *mut WrapFrameStack,
+    pixels: *mut u8,
+    pixels_len: usize,
+    channels_first: bool,
+) {
+    let &mut WrapFrameStack { ref mut stack } = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    let pixels = unsafe {
+        assert!(!pixels.is_null());
+        slice::from_raw_parts_mut(pixels, pixels_len)
+    };
+    if channels_first {
+        stack.write_channels_first(pixels);
+    } else {
+        stack.write_channels_last(pixels);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toybox;
+
+    #[test]
+    fn test_stack_resized_frames() {
+        let sim = toybox::get_simulation_by_name("breakout").unwrap();
+        let sim = Box::into_raw(Box::new(WrapSimulator { simulator: sim }));
+        let state = ::core::state_alloc(sim);
+        let stack = frame_stack_alloc(84, 84, true, 4);
+
+        let mut pixels = vec![0; 84 * 84 * 4];
+        frame_stack_write(stack, pixels.as_mut_ptr(), pixels.len(), false);
+        assert!(pixels.iter().all(|&p| p == 0));
+        frame_stack_push_frame(stack, sim, state);
+        frame_stack_write(stack, pixels.as_mut_ptr(), pixels.len(), false);
+        // Only the newest (last) channel has been drawn.
+        assert!(pixels.chunks(4).all(|px| px[0] == 0));
+        assert!(pixels.chunks(4).any(|px| px[3] != 0));
+
+        frame_stack_free(stack);
+        ::core::state_free(state);
+        ::core::simulator_free(sim);
+    }
+}
@@ -3,6 +3,7 @@
 extern crate amidar;
 extern crate breakout;
 extern crate libc;
+extern crate rayon;
 extern crate serde;
 extern crate serde_json;
 extern crate toybox;
@@ -20,5 +21,35 @@ pub struct WrapState {
     pub state: Box<toybox_core::State>,
 }
 
+/// This struct represents a StepWrapper (and the State inside it) as a single c void pointer.
+pub struct WrapStepWrapper {
+    pub wrapper: toybox_core::StepWrapper,
+}
+
+/// This struct represents a ReplayRecorder as a single c void pointer.
+pub struct WrapReplayRecorder {
+    pub recorder: toybox_core::replay::ReplayRecorder,
+}
+
+/// This struct represents a FrameStack as a single c void pointer.
+pub struct WrapFrameStack {
+    pub stack: toybox_core::graphics::FrameStack,
+}
+
 mod core;
 pub use core::*;
+
+mod error;
+pub use error::*;
+
+mod vec_env;
+pub use vec_env::*;
+
+mod step_wrapper;
+pub use step_wrapper::*;
+
+mod replay;
+pub use replay::*;
+
+mod frame_stack;
+pub use frame_stack::*;
@@ -0,0 +1,100 @@
+use super::set_last_error;
+use super::WrapReplayRecorder;
+use super::WrapSimulator;
+use super::WrapState;
+use libc::c_char;
+use std::ffi::{CStr, CString};
+use std::ptr;
+use toybox_core::replay::{Replay, ReplayError, ReplayRecorder};
+use toybox_core::{AleAction, LoadError};
+
+/// Start recording an episode from the given state; a checkpoint is taken every checkpoint_every frames (zero for only start and end).
+#[no_mangle]
+pub extern "C" fn replay_recorder_alloc(
+    sim_ptr: *mut WrapSimulator,
+    state_ptr: *mut WrapState,
+    checkpoint_every: usize,
+) -> *mut WrapReplayRecorder {
+    let &mut WrapSimulator { ref mut simulator } = unsafe {
+        assert!(!sim_ptr.is_null());
+        &mut *sim_ptr
+    };
+    let &mut WrapState { ref mut state } = unsafe {
+        assert!(!state_ptr.is_null());
+        &mut *state_ptr
+    };
+    let recorder = ReplayRecorder::new(simulator.as_ref(), state.as_ref(), checkpoint_every);
+    Box::into_raw(Box::new(WrapReplayRecorder { recorder }))
+}
+
+#[no_mangle]
+pub extern "C" fn replay_recorder_free(ptr: *mut WrapReplayRecorder) {
+    if ptr.is_null() {
+        return;
+    }
+    unsafe {
+        Box::from_raw(ptr);
+    }
+}
+
+/// Apply an ALE action to the state (as state_apply_ale_action does) and record it.
+#[no_mangle]
+pub extern "C" fn replay_recorder_apply_ale_action(
+    ptr: *mut WrapReplayRecorder,
+    state_ptr: *mut WrapState,
+    input: i32,
+) -> bool {
+    let &mut WrapReplayRecorder { ref mut recorder } = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    let &mut WrapState { ref mut state } = unsafe {
+        assert!(!state_ptr.is_null());
+        &mut *state_ptr
+    };
+    if let Some(input) = AleAction::from_int(input).map(|a| a.to_input()) {
+        recorder.step(state.as_mut(), input);
+        true
+    } else {
+        false
+    }
+}
+
+/// The episode recorded so far, as replay JSON.
+#[no_mangle]
+pub extern "C" fn replay_recorder_to_json(ptr: *mut WrapReplayRecorder) -> *const c_char {
+    let &mut WrapReplayRecorder { ref mut recorder } = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    let cjson: CString =
+        CString::new(recorder.replay().to_json()).expect("Conversion to CString should succeed!");
+    CString::into_raw(cjson)
+}
+
+/// Re-simulate replay JSON with any simulator of the same game, returning the final state.
+/// Returns NULL if the replay cannot be loaded or does not match its checkpoints; see last_error_code and last_error_message.
+#[no_mangle]
+pub extern "C" fn replay_play(
+    sim_ptr: *mut WrapSimulator,
+    replay_json: *const c_char,
+) -> *mut WrapState {
+    let &mut WrapSimulator { ref mut simulator } = unsafe {
+        assert!(!sim_ptr.is_null());
+        &mut *sim_ptr
+    };
+    let replay_json: &CStr = unsafe { CStr::from_ptr(replay_json) };
+    let played = replay_json
+        .to_str()
+        .map_err(|e| LoadError::ParseError(format!("{}", e)))
+        .and_then(Replay::from_json)
+        .map_err(ReplayError::from)
+        .and_then(|replay| replay.play(simulator.as_ref()));
+    match played {
+        Ok(state) => Box::into_raw(Box::new(WrapState { state })),
+        Err(e) => {
+            set_last_error(e);
+            ptr::null_mut()
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use super::set_last_error;
+use super::WrapSimulator;
+use super::WrapStepWrapper;
+use libc::c_char;
+use serde_json;
+use std::ffi::{CStr, CString};
+use std::ptr;
+use toybox_core::{AleAction, LoadError, StepWrapper, StepWrapperConfig};
+
+/// Start a new game wrapped according to config_json (a StepWrapperConfig); the seed drives sticky actions.
+/// Returns NULL if the config cannot be loaded; see last_error_code and last_error_message.
+#[no_mangle]
+pub extern "C" fn step_wrapper_alloc(
+    sim_ptr: *mut WrapSimulator,
+    config_json: *const c_char,
+    seed: u32,
+) -> *mut WrapStepWrapper {
+    let &mut WrapSimulator { ref mut simulator } = unsafe {
+        assert!(!sim_ptr.is_null());
+        &mut *sim_ptr
+    };
+    let config_json: &CStr = unsafe { CStr::from_ptr(config_json) };
+    let loaded = config_json
+        .to_str()
+        .map_err(|e| LoadError::ParseError(format!("{}", e)))
+        .and_then(|json_str| {
+            serde_json::from_str::<StepWrapperConfig>(json_str).map_err(LoadError::from)
+        })
+        .and_then(|config| {
+            config.check().map_err(LoadError::InvalidConfig)?;
+            Ok(config)
+        });
+    match loaded {
+        Ok(config) => {
+            let wrapper = StepWrapper::new_game(simulator.as_mut(), config, seed);
+            Box::into_raw(Box::new(WrapStepWrapper { wrapper }))
+        }
+        Err(e) => {
+            set_last_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn step_wrapper_free(ptr: *mut WrapStepWrapper) {
+    if ptr.is_null() {
+        return;
+    }
+    unsafe {
+        Box::from_raw(ptr);
+    }
+}
+
+/// Apply an ALE action for the configured number of frames, storing the change in score in reward.
+/// Returns false (and does nothing) if the action is not a legal ALE action.
+#[no_mangle]
+pub extern "C" fn step_wrapper_apply_ale_action(
+    ptr: *mut WrapStepWrapper,
+    input: i32,
+    reward: *mut i32,
+) -> bool {
+    let &mut WrapStepWrapper { ref mut wrapper } = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    if let Some(input) = AleAction::from_int(input).map(|a| a.to_input()) {
+        let delta = wrapper.step(input);
+        if !reward.is_null() {
+            unsafe { *reward = delta };
+        }
+        true
+    } else {
+        false
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn step_wrapper_lives(ptr: *mut WrapStepWrapper) -> i32 {
+    let &mut WrapStepWrapper { ref mut wrapper } = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    wrapper.lives()
+}
+
+#[no_mangle]
+pub extern "C" fn step_wrapper_score(ptr: *mut WrapStepWrapper) -> i32 {
+    let &mut WrapStepWrapper { ref mut wrapper } = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    wrapper.score()
+}
+
+/// Render the (possibly max-pooled) frame; pixels_len must be width * height * (1 if grayscale else 4).
+#[no_mangle]
+pub extern "C" fn step_wrapper_render(
+    ptr: *mut WrapStepWrapper,
+    pixels: *mut u8,
+    pixels_len: usize,
+    grayscale: bool,
+) {
+    let &mut WrapStepWrapper { ref mut wrapper } = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    let imgdata = if grayscale {
+        wrapper.render_grayscale().data
+    } else {
+        wrapper.render().data
+    };
+    assert_eq!(pixels_len, imgdata.len());
+    let pixels = unsafe {
+        assert!(!pixels.is_null());
+        ::std::slice::from_raw_parts_mut(pixels, pixels_len)
+    };
+    pixels.copy_from_slice(&imgdata);
+}
+
+#[no_mangle]
+pub extern "C" fn step_wrapper_to_json(ptr: *mut WrapStepWrapper) -> *const c_char {
+    let &mut WrapStepWrapper { ref mut wrapper } = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    let cjson: CString =
+        CString::new(wrapper.to_json()).expect("Conversion to CString should succeed!");
+    CString::into_raw(cjson)
+}
+
+/// Returns NULL if the JSON cannot be loaded; see last_error_code and last_error_message.
+#[no_mangle]
+pub extern "C" fn step_wrapper_from_json(
+    sim_ptr: *mut WrapSimulator,
+    json_str: *const c_char,
+) -> *mut WrapStepWrapper {
+    let &mut WrapSimulator { ref mut simulator } = unsafe {
+        assert!(!sim_ptr.is_null());
+        &mut *sim_ptr
+    };
+    let json_str: &CStr = unsafe { CStr::from_ptr(json_str) };
+    let loaded = json_str
+        .to_str()
+        .map_err(|e| LoadError::ParseError(format!("{}", e)))
+        .and_then(|json_str| StepWrapper::from_json(simulator.as_ref(), json_str));
+    match loaded {
+        Ok(wrapper) => Box::into_raw(Box::new(WrapStepWrapper { wrapper })),
+        Err(e) => {
+            set_last_error(e);
+            ptr::null_mut()
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use super::WrapSimulator;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::slice;
+use toybox_core::graphics::{GrayscaleBuffer, ImageBuffer};
+use toybox_core::{AleAction, Input, Simulation, State};
+
+/// A batch of states of the same Simulation, stepped together so that learners pay one FFI call per batch rather than one per state.
+/// Any state that reaches game over (lives() < 0) is replaced with a new game as part of the step that ended it.
+pub struct VecEnv {
+    /// The config every state in this batch was created from; it is also used for resets.
+    simulator: Box<Simulation>,
+    states: Vec<Box<State>>,
+    /// When present, states are stepped and rendered on this pool; otherwise on the calling thread.
+    pool: Option<ThreadPool>,
+}
+
+impl VecEnv {
+    /// Create `count` new games; with `num_threads` > 1, steps and renders are spread across that many worker threads.
+    pub fn new(mut simulator: Box<Simulation>, count: usize, num_threads: usize) -> VecEnv {
+        let states = (0..count).map(|_| simulator.new_game()).collect();
+        let pool = if num_threads > 1 {
+            Some(
+                ThreadPoolBuilder::new()
+                    .num_threads(num_threads)
+                    .build()
+                    .expect("Could not start VecEnv thread pool."),
+            )
+        } else {
+            None
+        };
+        VecEnv {
+            simulator,
+            states,
+            pool,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    pub fn states(&self) -> &[Box<State>] {
+        &self.states
+    }
+
+    /// The number of bytes in one rendered frame.
+    pub fn frame_size(&self, grayscale: bool) -> usize {
+        let (w, h) = self.simulator.game_size();
+        let channels = if grayscale { 1 } else { 4 };
+        (w * h * channels) as usize
+    }
+
+    /// Replace every state with a new game.
+    pub fn reset(&mut self) {
+        for state in self.states.iter_mut() {
+            *state = self.simulator.new_game();
+        }
+    }
+
+    /// Apply one ALE action to each state, writing the change in score to `rewards` and whether that game ended to `dones`.
+    /// Returns false without stepping anything if any action is not a legal ALE action.
+    pub fn step(&mut self, actions: &[i32], rewards: &mut [i32], dones: &mut [bool]) -> bool {
+        assert_eq!(actions.len(), self.states.len());
+        assert_eq!(rewards.len(), self.states.len());
+        assert_eq!(dones.len(), self.states.len());
+
+        let inputs: Option<Vec<Input>> = actions
+            .iter()
+            .map(|&a| AleAction::from_int(a).map(|a| a.to_input()))
+            .collect();
+        let inputs = match inputs {
+            Some(inputs) => inputs,
+            None => return false,
+        };
+
+        let step_one =
+            |state: &mut Box<State>, input: &Input, reward: &mut i32, done: &mut bool| {
+                let before = state.score();
+                state.update_mut(*input);
+                *reward = state.score() - before;
+                *done = state.lives() < 0;
+            };
+        let states = &mut self.states;
+        match self.pool {
+            Some(ref pool) => pool.install(|| {
+                states
+                    .par_iter_mut()
+                    .zip(inputs.par_iter())
+                    .zip(rewards.par_iter_mut().zip(dones.par_iter_mut()))
+                    .for_each(|((state, input), (reward, done))| {
+                        step_one(state, input, reward, done)
+                    })
+            }),
+            None => states
+                .iter_mut()
+                .zip(inputs.iter())
+                .zip(rewards.iter_mut().zip(dones.iter_mut()))
+                .for_each(|((state, input), (reward, done))| step_one(state, input, reward, done)),
+        }
+
+        // New games draw from the simulator's random stream, so they are created in order on this thread.
+        for (state, done) in states.iter_mut().zip(dones.iter()) {
+            if *done {
+                *state = self.simulator.new_game();
+            }
+        }
+        true
+    }
+
+    /// Render every state, one frame after another, into `pixels` (which must be len() * frame_size(grayscale) bytes).
+    /// This takes &mut self only because States are Send but not Sync.
+    pub fn render(&mut self, pixels: &mut [u8], grayscale: bool) {
+        let frame_size = self.frame_size(grayscale);
+        assert_eq!(pixels.len(), frame_size * self.states.len());
+        let (w, h) = self.simulator.game_size();
+
+        let render_one = |(state, frame): (&mut Box<State>, &mut [u8])| {
+            if grayscale {
+                let mut img = GrayscaleBuffer::alloc(w, h);
+                img.render(&state.draw());
+                frame.copy_from_slice(&img.data);
+            } else {
+                let mut img = ImageBuffer::alloc(w, h);
+                img.render(&state.draw());
+                frame.copy_from_slice(&img.data);
+            }
+        };
+        let states = &mut self.states;
+        match self.pool {
+            Some(ref pool) => pool.install(|| {
+                states
+                    .par_iter_mut()
+                    .zip(pixels.par_chunks_mut(frame_size))
+                    .for_each(render_one)
+            }),
+            None => states
+                .iter_mut()
+                .zip(pixels.chunks_mut(frame_size))
+                .for_each(render_one),
+        }
+    }
+}
+
+/// Start `count` games of the given simulator's config; the VecEnv keeps its own copy of the config.
+#[no_mangle]
+pub extern "C" fn vec_env_alloc(
+    sim_ptr: *mut WrapSimulator,
+    count: usize,
+    num_threads: usize,
+) -> *mut VecEnv {
+    let &mut WrapSimulator { ref mut simulator } = unsafe {
+        assert!(!sim_ptr.is_null());
+        &mut *sim_ptr
+    };
+    let simulator = simulator
+        .from_json(&simulator.to_json())
+        .expect("A Simulation should be able to load its own config.");
+    Box::into_raw(Box::new(VecEnv::new(simulator, count, num_threads)))
+}
+
+#[no_mangle]
+pub extern "C" fn vec_env_free(ptr: *mut VecEnv) {
+    if ptr.is_null() {
+        return;
+    }
+    unsafe {
+        Box::from_raw(ptr);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn vec_env_len(ptr: *mut VecEnv) -> usize {
+    let env = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    env.len()
+}
+
+#[no_mangle]
+pub extern "C" fn vec_env_reset(ptr: *mut VecEnv) {
+    let env = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    env.reset()
+}
+
+/// All three arrays must have vec_env_len entries. Returns false (and steps nothing) if any action is not a legal ALE action.
+#[no_mangle]
+pub extern "C" fn vec_env_step(
+    ptr: *mut VecEnv,
+    actions: *const i32,
+    rewards: *mut i32,
+    dones: *mut bool,
+) -> bool {
+    let env = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    let n = env.len();
+    let (actions, rewards, dones) = unsafe {
+        assert!(!actions.is_null() && !rewards.is_null() && !dones.is_null());
+        (
+            slice::from_raw_parts(actions, n),
+            slice::from_raw_parts_mut(rewards, n),
+            slice::from_raw_parts_mut(dones, n),
+        )
+    };
+    env.step(actions, rewards, dones)
+}
+
+#[no_mangle]
+pub extern "C" fn vec_env_scores(ptr: *mut VecEnv, scores: *mut i32) {
+    let env = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    let scores = unsafe {
+        assert!(!scores.is_null());
+        slice::from_raw_parts_mut(scores, env.len())
+    };
+    for (out, state) in scores.iter_mut().zip(env.states()) {
+        *out = state.score();
+    }
+}
+
+/// Frames are written back to back, each width * height * (1 if grayscale else 4) bytes.
+#[no_mangle]
+pub extern "C" fn vec_env_render(
+    ptr: *mut VecEnv,
+    pixels: *mut u8,
+    pixels_len: usize,
+    grayscale: bool,
+) {
+    let env = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+    let pixels = unsafe {
+        assert!(!pixels.is_null());
+        slice::from_raw_parts_mut(pixels, pixels_len)
+    };
+    env.render(pixels, grayscale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toybox;
+
+    #[test]
+    fn test_step_and_render() {
+        let sim = toybox::get_simulation_by_name("breakout").unwrap();
+        for &threads in &[0, 2] {
+            let sim = sim.from_json(&sim.to_json()).unwrap();
+            let mut env = VecEnv::new(sim, 3, threads);
+            let mut rewards = vec![0; 3];
+            let mut dones = vec![false; 3];
+            assert!(env.step(&[0, 1, 3], &mut rewards, &mut dones));
+            assert!(!env.step(&[0, 1, 999], &mut rewards, &mut dones));
+
+            let mut pixels = vec![0; env.frame_size(true) * 3];
+            env.render(&mut pixels, true);
+            assert!(pixels.iter().any(|&p| p != 0));
+        }
+    }
+
+    #[test]
+    fn test_auto_reset() {
+        let sim = toybox::get_simulation_by_name("breakout").unwrap();
+        let mut env = VecEnv::new(sim, 2, 0);
+        let mut rewards = vec![0; 2];
+        let mut dones = vec![false; 2];
+        // Serving without ever moving loses every ball eventually.
+        let mut steps = 0;
+        while !dones[0] {
+            assert!(env.step(&[1, 1], &mut rewards, &mut dones));
+            steps += 1;
+            assert!(steps < 100_000);
+        }
+        assert!(env.states()[0].lives() >= 0);
+    }
+}
@@ -60,6 +60,8 @@ except:
 
 class Input():
     """An input object represents a game controller having left, right, up, down, and two buttons.
+    Games whose action space has a paddle (see Simulator.action_space) also read `paddle`,
+    an analog position from 0.0 (far left) to 1.0 (far right); leave it None otherwise.
 
     ALE mapping:
             ALE_ACTION_MEANING = {
@@ -102,6 +104,7 @@ class Input():
         self.down = False
         self.button1 = False
         self.button2 = False
+        self.paddle = None
 
     def __str__(self):
         return self.__dict__.__str__()
@@ -146,6 +149,14 @@ def rust_str(result):
     return txt
 
 
+def rust_last_error():
+    """Build an exception from the most recent error reported by the rust library."""
+    msg = lib.last_error_message()
+    if msg == ffi.NULL:
+        return ValueError('Unknown error in toybox.')
+    return ValueError(rust_str(msg))
+
+
 def json_str(js):
     if type(js) is dict:
         js = json.dumps(js)
@@ -159,9 +170,12 @@ class Simulator(object):
     def __init__(self, game_name, sim=None):
         if sim is None:
             sim = lib.simulator_alloc(game_name.encode('utf-8'))
+            if sim == ffi.NULL:
+                raise rust_last_error()
         # sim should be a pointer
         self.game_name = game_name
         self.__sim = sim 
+        self.__palette = None
         self.deleted = False
 
     def __del__(self):
@@ -179,6 +193,10 @@ class Simulator(object):
     def set_seed(self, value):
         lib.simulator_seed(self.__sim, value)
 
+    def set_seed_u64(self, value):
+        """Seed from 64 bits; unlike set_seed, nearby seeds give unrelated games."""
+        lib.simulator_seed_u64(self.__sim, value)
+
     def get_frame_width(self):
         return lib.simulator_frame_width(self.__sim)
 
@@ -193,15 +211,51 @@ class Simulator(object):
 
     def state_from_json(self, js):
         state = lib.state_from_json(self.get_simulator(), json_str(js).encode('utf-8'))
+        if state == ffi.NULL:
+            raise rust_last_error()
         return State(self, state=state)
 
     def to_json(self):
         json_str = rust_str(lib.simulator_to_json(self.get_simulator()))
         return json.loads(str(json_str))
 
+    def validate(self):
+        """Return a list of problems with this config; an empty list means it looks fine."""
+        return json.loads(rust_str(lib.simulator_validate(self.get_simulator())))
+
+    def object_kinds(self):
+        """The kinds of object this game's states report, in the order used by State.objects_tensor."""
+        return json.loads(rust_str(lib.simulator_object_kinds(self.get_simulator())))
+
+    def action_space(self):
+        """This game's controls: {"actions": [...], "paddle": bool}, where each action is a dict
+        with its "name", the "input" it presses, and the "noop_when" situations where it does nothing."""
+        return json.loads(rust_str(lib.simulator_action_space(self.get_simulator())))
+
+    def action_names(self):
+        """The name of each discrete action, in the same order as get_legal_action_set."""
+        return [action['name'] for action in self.action_space()['actions']]
+
+    def palette(self):
+        """Every color this game draws, as a list of {r, g, b, a} dicts; State.render_frame_palette writes indices into it."""
+        if self.__palette is None:
+            self.__palette = json.loads(rust_str(lib.simulator_palette(self.get_simulator())))
+        return list(self.__palette)
+
+    def play_replay(self, replay_js):
+        """Re-simulate a recorded episode, checking its checkpoints; returns the final State."""
+        state = lib.replay_play(self.get_simulator(), json_str(replay_js).encode('utf-8'))
+        if state == ffi.NULL:
+            raise rust_last_error()
+        return State(self, state=state)
+
     def from_json(self, config_js):
+        new_sim = lib.simulator_from_json(self.get_simulator(), json_str(config_js).encode('utf-8'))
+        if new_sim == ffi.NULL:
+            raise rust_last_error()
         old_sim = self.__sim
-        self.__sim = lib.simulator_from_json(self.get_simulator(), json_str(config_js).encode('utf-8'))
+        self.__sim = new_sim
+        self.__palette = None
         del old_sim
 
 
@@ -236,6 +290,43 @@ class State(object):
     def game_over(self):
         return self.lives() < 0
 
+    def step(self, action_int):
+        """Apply an ALE action and return a dict with reward, life_lost, level_complete, game_over and events.
+        The events are those drain_events would have returned, and are removed from the state's log."""
+        result = lib.state_step_ale_action(self.__state, action_int)
+        if result == ffi.NULL:
+            raise ValueError("Expected to apply action, but failed: {0}".format(action_int))
+        return json.loads(rust_str(result))
+
+    def clone(self):
+        """Copy this state in rust; much faster than a round trip through JSON."""
+        copy = State.__new__(State)
+        copy.__state = lib.state_clone(self.__state)
+        copy.game_name = self.game_name
+        copy.deleted = False
+        return copy
+
+    def state_hash(self):
+        """A 64-bit hash of this state that is stable across runs."""
+        return lib.state_hash(self.__state)
+
+    def drain_events(self):
+        """Remove and return the game events (dicts with kind, frame and payload) logged since the last call."""
+        return json.loads(rust_str(lib.state_drain_events(self.__state)))
+
+    def objects(self):
+        """Return the entities in this frame as dicts with kind, bounding box (x, y, w, h), velocity and attributes."""
+        return json.loads(rust_str(lib.state_objects_json(self.__state)))
+
+    def objects_tensor(self, sim, max_objects):
+        """Return the entities in this frame as a fixed-size (max_objects, row_width) float32 array."""
+        width = lib.simulator_object_row_width(sim.get_simulator())
+        size = max_objects * width
+        tensor = np.zeros(size, dtype='float32')
+        lib.state_objects_tensor(sim.get_simulator(), self.__state, max_objects,
+                                 ffi.cast("float *", tensor.ctypes.data), size)
+        return np.reshape(tensor, (max_objects, width))
+
     def query_json(self, query, args="null"):
         txt = rust_str(lib.state_query_json(self.__state, json_str(query).encode('utf-8'), json_str(args).encode('utf-8')))
         try:
@@ -273,10 +364,242 @@ class State(object):
         lib.render_current_frame(frame_ptr, size, True, sim.get_simulator(), self.__state)
         return np.reshape(frame, (h,w,1))
 
+    def render_frame_palette(self, sim):
+        """Return (indices, palette): one palette index per pixel with shape (h, w, 1), and the palette, which is sim.palette() plus any colors missing from it."""
+        h = sim.get_frame_height()
+        w = sim.get_frame_width()
+        size = h * w
+        frame = np.zeros(size, dtype='uint8')
+        frame_ptr = ffi.cast("uint8_t *", frame.ctypes.data)
+        grown = lib.render_current_frame_palette(frame_ptr, size, sim.get_simulator(), self.__state)
+        palette = sim.palette() if grown == ffi.NULL else json.loads(rust_str(grown))
+        return np.reshape(frame, (h, w, 1)), palette
+
+    def render_frame_resized(self, sim, width=84, height=84, grayscale=True, crop=None, method='area'):
+        """Render at width x height, e.g., for DQN; crop is an optional (x, y, w, h) region and method is one of 'nearest', 'bilinear' or 'area'."""
+        methods = {'nearest': 0, 'bilinear': 1, 'area': 2}
+        if method not in methods:
+            raise ValueError('Unknown resampling method: %s (try one of %s)' % (method, list(methods)))
+        crop_x, crop_y, crop_w, crop_h = crop or (0, 0, 0, 0)
+        channels = 1 if grayscale else 4
+        size = height * width * channels
+        frame = np.zeros(size, dtype='uint8')
+        frame_ptr = ffi.cast("uint8_t *", frame.ctypes.data)
+        lib.render_current_frame_resized(frame_ptr, size, grayscale, sim.get_simulator(), self.__state,
+                                         crop_x, crop_y, crop_w, crop_h, width, height, methods[method])
+        return np.reshape(frame, (height, width, channels))
+
+    def render_mask(self, sim):
+        """Return (class_ids, instance_ids) arrays of shape (h, w); class id i > 0 is sim.object_kinds()[i-1], and 0 is background."""
+        h = sim.get_frame_height()
+        w = sim.get_frame_width()
+        size = h * w
+        class_ids = np.zeros(size, dtype='uint8')
+        instance_ids = np.zeros(size, dtype='int32')
+        lib.render_mask(ffi.cast("uint8_t *", class_ids.ctypes.data),
+                        ffi.cast("int32_t *", instance_ids.ctypes.data),
+                        size, sim.get_simulator(), self.__state)
+        return np.reshape(class_ids, (h, w)), np.reshape(instance_ids, (h, w))
+
+    def render_svg(self, sim, scale=1.0, annotate=False):
+        """Return the current frame as an SVG document; with annotate, every object is outlined and labelled with its kind."""
+        return rust_str(lib.render_svg(sim.get_simulator(), self.__state, scale, annotate))
+
     def to_json(self):
         json_str = rust_str(lib.state_to_json(self.__state))
         return json.loads(str(json_str))
 
+    def to_text(self):
+        """A game-specific plain-text picture of this state, for printing."""
+        return rust_str(lib.state_to_text(self.__state))
+
+class ReplayRecorder(object):
+    """Records the actions applied to a State so that the episode can be replayed exactly with Simulator.play_replay."""
+    def __init__(self, sim, state, checkpoint_every=100):
+        self.__state = state
+        self.__recorder = lib.replay_recorder_alloc(sim.get_simulator(), state.get_state(), checkpoint_every)
+        self.deleted = False
+
+    def __enter__(self):
+        return self
+
+    def __del__(self):
+        if not self.deleted:
+            self.deleted = True
+            lib.replay_recorder_free(self.__recorder)
+            self.__recorder = None
+
+    def __exit__(self, exc_type, exc_value, traceback):
+        self.__del__()
+
+    def apply_ale_action(self, action_int):
+        if not lib.replay_recorder_apply_ale_action(self.__recorder, self.__state.get_state(), action_int):
+            raise ValueError("Expected to apply action, but failed: {0}".format(action_int))
+
+    def to_json(self):
+        return json.loads(rust_str(lib.replay_recorder_to_json(self.__recorder)))
+
+
+class StepWrapper(object):
+    """A game whose actions are applied in rust with frameskip, sticky actions and max-pooled frames.
+
+    config is a dict with frameskip, max_pool and repeat_action_probability; the seed drives sticky actions.
+    """
+    def __init__(self, sim, config, seed=0, wrapper=None):
+        if wrapper is None:
+            wrapper = lib.step_wrapper_alloc(sim.get_simulator(), json_str(config).encode('utf-8'), seed)
+            if wrapper == ffi.NULL:
+                raise rust_last_error()
+        self.__sim = sim
+        self.__wrapper = wrapper
+        self.deleted = False
+
+    def __enter__(self):
+        return self
+
+    def __del__(self):
+        if not self.deleted:
+            self.deleted = True
+            lib.step_wrapper_free(self.__wrapper)
+            self.__wrapper = None
+
+    def __exit__(self, exc_type, exc_value, traceback):
+        self.__del__()
+
+    def apply_ale_action(self, action_int):
+        """Apply an action for frameskip frames and return the change in score."""
+        reward = ffi.new("int32_t *")
+        if not lib.step_wrapper_apply_ale_action(self.__wrapper, action_int, reward):
+            raise ValueError("Expected to apply action, but failed: {0}".format(action_int))
+        return reward[0]
+
+    def lives(self):
+        return lib.step_wrapper_lives(self.__wrapper)
+
+    def score(self):
+        return lib.step_wrapper_score(self.__wrapper)
+
+    def game_over(self):
+        return self.lives() < 0
+
+    def render_frame(self, grayscale=True):
+        h = self.__sim.get_frame_height()
+        w = self.__sim.get_frame_width()
+        channels = 1 if grayscale else 4
+        size = h * w * channels
+        frame = np.zeros(size, dtype='uint8')
+        lib.step_wrapper_render(self.__wrapper, ffi.cast("uint8_t *", frame.ctypes.data), size, grayscale)
+        return np.reshape(frame, (h, w, channels))
+
+    def to_json(self):
+        return json.loads(rust_str(lib.step_wrapper_to_json(self.__wrapper)))
+
+    @staticmethod
+    def from_json(sim, js):
+        wrapper = lib.step_wrapper_from_json(sim.get_simulator(), json_str(js).encode('utf-8'))
+        if wrapper == ffi.NULL:
+            raise rust_last_error()
+        return StepWrapper(sim, None, wrapper=wrapper)
+
+
+class VecEnv(object):
+    """A batch of games of one Simulator, stepped and rendered together in rust.
+
+    Finished games (lives < 0) are replaced with new games by the step that ended them.
+    """
+    def __init__(self, sim, count, num_threads=0):
+        self.__env = lib.vec_env_alloc(sim.get_simulator(), count, num_threads)
+        self.count = count
+        self.width = sim.get_frame_width()
+        self.height = sim.get_frame_height()
+        self.deleted = False
+
+    def __enter__(self):
+        return self
+
+    def __del__(self):
+        if not self.deleted:
+            self.deleted = True
+            lib.vec_env_free(self.__env)
+            self.__env = None
+
+    def __exit__(self, exc_type, exc_value, traceback):
+        self.__del__()
+
+    def __len__(self):
+        return self.count
+
+    def reset(self):
+        lib.vec_env_reset(self.__env)
+
+    def step(self, actions):
+        """Apply one ALE action int per game; returns (rewards, dones) arrays."""
+        actions = np.ascontiguousarray(actions, dtype='int32')
+        assert(actions.shape == (self.count,))
+        rewards = np.zeros(self.count, dtype='int32')
+        dones = np.zeros(self.count, dtype='bool')
+        ok = lib.vec_env_step(self.__env,
+                              ffi.cast("int32_t *", actions.ctypes.data),
+                              ffi.cast("int32_t *", rewards.ctypes.data),
+                              ffi.cast("bool *", dones.ctypes.data))
+        if not ok:
+            raise ValueError("Expected to apply actions, but failed: {0}".format(actions))
+        return rewards, dones
+
+    def scores(self):
+        scores = np.zeros(self.count, dtype='int32')
+        lib.vec_env_scores(self.__env, ffi.cast("int32_t *", scores.ctypes.data))
+        return scores
+
+    def render(self, grayscale=True):
+        """Returns every game's frame as one (count, height, width, channels) array."""
+        channels = 1 if grayscale else 4
+        size = self.count * self.height * self.width * channels
+        frames = np.zeros(size, dtype='uint8')
+        lib.vec_env_render(self.__env, ffi.cast("uint8_t *", frames.ctypes.data), size, grayscale)
+        return np.reshape(frames, (self.count, self.height, self.width, channels))
+
+
+class FrameStack(object):
+    """Keeps the last depth frames of a game in rust, e.g., the 4 stacked 84x84 grayscale frames of DQN."""
+    def __init__(self, sim, depth=4, width=None, height=None, grayscale=True):
+        self.sim = sim
+        self.depth = depth
+        self.width = width or sim.get_frame_width()
+        self.height = height or sim.get_frame_height()
+        self.channels = 1 if grayscale else 4
+        self.__stack = lib.frame_stack_alloc(self.width, self.height, grayscale, depth)
+        self.deleted = False
+
+    def __enter__(self):
+        return self
+
+    def __del__(self):
+        if not self.deleted:
+            self.deleted = True
+            lib.frame_stack_free(self.__stack)
+            self.__stack = None
+
+    def __exit__(self, exc_type, exc_value, traceback):
+        self.__del__()
+
+    def clear(self):
+        lib.frame_stack_clear(self.__stack)
+
+    def push(self, state):
+        """Render state and add it as the newest frame, dropping the oldest."""
+        lib.frame_stack_push_frame(self.__stack, self.sim.get_simulator(), state.get_state())
+
+    def get(self, channels_first=False):
+        """Return every frame, oldest first, as (h, w, depth*channels) or, if channels_first, (depth*channels, h, w)."""
+        size = self.depth * self.channels * self.height * self.width
+        frames = np.zeros(size, dtype='uint8')
+        lib.frame_stack_write(self.__stack, ffi.cast("uint8_t *", frames.ctypes.data), size, channels_first)
+        if channels_first:
+            return np.reshape(frames, (self.depth * self.channels, self.height, self.width))
+        return np.reshape(frames, (self.height, self.width, self.depth * self.channels))
+
+
 class Toybox(object):
     def __init__(self, game_name, grayscale=True, frameskip=0):
         self.game_name = game_name
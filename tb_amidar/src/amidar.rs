@@ -1,10 +1,13 @@
 use super::digit_sprites::{draw_score, DIGIT_HEIGHT};
 use serde_json;
-use std::collections::{HashSet, VecDeque};
+use std::collections::{BTreeSet, VecDeque};
 use toybox_core;
-use toybox_core::graphics::{Color, Drawable, FixedSpriteData};
+use toybox_core::graphics::{unique_colors, Color, Drawable, FixedSpriteData};
 use toybox_core::random;
-use toybox_core::{AleAction, Direction, Input, QueryError};
+use toybox_core::{
+    Action, ActionSpace, AleAction, Direction, Event, EventLog, Input, LoadError, Object,
+    QueryError, StepResult,
+};
 use types::*;
 
 use rand::seq::SliceRandom;
@@ -79,6 +82,13 @@ pub mod images {
     }
 }
 
+mod kinds {
+    pub const NAMES: &[&str] = &["player", "enemy", "box"];
+    pub const PLAYER: u8 = 1;
+    pub const ENEMY: u8 = 2;
+    pub const BOX: u8 = 3;
+}
+
 mod world {
     use super::screen;
     pub const SCALE: i32 = 16;
@@ -645,6 +655,9 @@ impl Board {
             // Exit function if row is errorful.
             tiles.push(row?);
         }
+        if tiles.is_empty() || tiles[0].is_empty() {
+            return Err("Cannot construct an empty AmidarBoard.".to_owned());
+        }
         let width = tiles[0].len() as u32;
         let height = tiles.len() as u32;
 
@@ -652,9 +665,10 @@ impl Board {
             tiles,
             width,
             height,
-            junctions: HashSet::new(),
-            chase_junctions: HashSet::new(),
+            junctions: BTreeSet::new(),
+            chase_junctions: BTreeSet::new(),
             boxes: Vec::new(),
+            events: EventLog::default(),
         };
         board.init_junctions();
         debug_assert!(board.boxes.is_empty());
@@ -874,10 +888,19 @@ impl Board {
         let updated = indices.len() as i32;
         let mut chase_change = false;
         for i in indices {
-            self.boxes[i].painted = true;
-            if self.boxes[i].triggers_chase {
+            let b = &mut self.boxes[i];
+            b.painted = true;
+            if b.triggers_chase {
                 chase_change = true;
             }
+            self.events.push(
+                "box_painted",
+                json!({
+                    "top_left": b.top_left,
+                    "bottom_right": b.bottom_right,
+                    "triggers_chase": b.triggers_chase,
+                }),
+            );
         }
 
         let triggers_chase = chase_change
@@ -992,7 +1015,7 @@ impl State {
         let player = Mob::new_player(config.player_start.to_world(), config.player_speed);
 
         let core = StateCore {
-            rand: random::Gen::new_child(&mut config.rand),
+            rand: random::Streams::new(random::Gen::new_child(&mut config.rand)),
             lives: config.start_lives,
             score: 0,
             chase_timer: 0,
@@ -1002,6 +1025,7 @@ impl State {
             player,
             enemies,
             board,
+            events: EventLog::default(),
         };
 
         let mut state = State {
@@ -1060,6 +1084,9 @@ impl toybox_core::Simulation for Amidar {
     fn reset_seed(&mut self, seed: u32) {
         self.rand.reset_seed(seed)
     }
+    fn reset_seed_u64(&mut self, seed: u64) {
+        self.rand.reset_seed_u64(seed)
+    }
     fn game_size(&self) -> (i32, i32) {
         screen::GAME_SIZE
     }
@@ -1069,6 +1096,28 @@ impl toybox_core::Simulation for Amidar {
     fn to_json(&self) -> String {
         serde_json::to_string(self).expect("Amidar should be JSON serializable!")
     }
+    /// The fire button jumps, which does nothing while already jumping, while chasing, or once the jumps run out, whatever direction goes with it.
+    fn action_space(&self) -> ActionSpace {
+        let actions = self
+            .legal_action_set()
+            .into_iter()
+            .map(|a| {
+                let action = Action::from_ale(a);
+                let name = action.name.replace("fire", "jump");
+                let action = action.with_name(&name);
+                if action.input.button1 {
+                    action
+                        .noop_when("jumping")
+                        .noop_when("chasing")
+                        .noop_when("no_jumps_left")
+                } else {
+                    action
+                }
+            })
+            .collect();
+        ActionSpace::new(actions)
+    }
+
     /// Sync with [ALE impl](https://github.com/mgbellemare/Arcade-Learning-Environment/blob/master/src/games/supported/Amidar.cpp#L80)
     /// Note, leaving a call to sort in this impl to remind users that these vecs are ordered!
     fn legal_action_set(&self) -> Vec<AleAction> {
@@ -1088,10 +1137,32 @@ impl toybox_core::Simulation for Amidar {
         actions
     }
 
-    fn new_state_from_json(
-        &self,
-        json_str: &str,
-    ) -> Result<Box<toybox_core::State>, serde_json::Error> {
+    fn object_kinds(&self) -> Vec<String> {
+        kinds::NAMES.iter().map(|k| k.to_string()).collect()
+    }
+
+    fn palette(&self) -> Vec<Color> {
+        let mut colors: Vec<Color> = self.colors().into_iter().cloned().collect();
+        let sprites: &[&FixedSpriteData] = &[
+            &images::PLAYER_L1,
+            &images::ENEMY_L1,
+            &images::ENEMY_CHASE_L1,
+            &images::BLOCK_TILE_PAINTED_L1,
+            &images::BLOCK_TILE_UNPAINTED_L1,
+            &images::PLAYER_L2,
+            &images::ENEMY_L2,
+            &images::ENEMY_CHASE_L2,
+            &images::BLOCK_TILE_PAINTED_L2,
+            &images::BLOCK_TILE_UNPAINTED_L2,
+            &images::PAINTED_BOX_BAR,
+        ];
+        for sprite in sprites {
+            colors.extend(sprite.colors());
+        }
+        unique_colors(colors)
+    }
+
+    fn new_state_from_json(&self, json_str: &str) -> Result<Box<toybox_core::State>, LoadError> {
         let state: StateCore = serde_json::from_str(json_str)?;
         Ok(Box::new(State {
             config: self.clone(),
@@ -1099,13 +1170,105 @@ impl toybox_core::Simulation for Amidar {
         }))
     }
 
-    fn from_json(
-        &self,
-        json_config: &str,
-    ) -> Result<Box<toybox_core::Simulation>, serde_json::Error> {
+    fn from_json(&self, json_config: &str) -> Result<Box<toybox_core::Simulation>, LoadError> {
         let config: Amidar = serde_json::from_str(json_config)?;
+        // Every new game parses the board, so reject a broken one up front.
+        Board::try_new(&config.board).map_err(LoadError::InvalidConfig)?;
         Ok(Box::new(config))
     }
+
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let board = match Board::try_new(&self.board) {
+            Ok(board) => board,
+            Err(msg) => {
+                // Nothing else can be checked without a board.
+                problems.push(msg);
+                return problems;
+            }
+        };
+        for (y, row) in self.board.iter().enumerate() {
+            let width = row.chars().count() as u32;
+            if width != board.width {
+                problems.push(format!(
+                    "board row {} has width {} but the first row has width {}.",
+                    y, width, board.width
+                ));
+            }
+        }
+
+        let start = &self.player_start;
+        if board.tile_id(start).is_none() {
+            problems.push(format!(
+                "player_start ({}, {}) is outside the board.",
+                start.tx, start.ty
+            ));
+        } else if !board.get_tile(start).walkable() {
+            problems.push(format!(
+                "player_start ({}, {}) is on an Empty tile.",
+                start.tx, start.ty
+            ));
+        }
+        if self.default_board_bugs && board.get_junction_id(&TilePoint::new(31, 18)).is_none() {
+            problems.push(
+                "default_board_bugs requires a junction at (31, 18); use false for custom boards."
+                    .to_owned(),
+            );
+        }
+
+        for (i, ai) in self.enemies.iter().enumerate() {
+            let start = match ai {
+                MovementAI::Player => {
+                    problems.push(format!("enemies[{}] uses the Player AI.", i));
+                    continue;
+                }
+                MovementAI::EnemyLookupAI {
+                    default_route_index,
+                    ..
+                } => {
+                    if (*default_route_index as usize) >= DEFAULT_ENEMY_ROUTES.len() {
+                        problems.push(format!(
+                            "enemies[{}] uses route {} but there are only {} default routes.",
+                            i,
+                            default_route_index,
+                            DEFAULT_ENEMY_ROUTES.len()
+                        ));
+                    }
+                    continue;
+                }
+                MovementAI::EnemyPerimeterAI { .. } => TilePoint::new(0, 0),
+                MovementAI::EnemyAmidarMvmt { start, .. }
+                | MovementAI::EnemyRandomMvmt { start, .. }
+                | MovementAI::EnemyTargetPlayer { start, .. } => start.clone(),
+            };
+            if !board.get_tile(&start).walkable() {
+                problems.push(format!(
+                    "enemies[{}] starts at ({}, {}), which is not walkable.",
+                    i, start.tx, start.ty
+                ));
+            }
+        }
+
+        if self.start_lives < 0 {
+            problems.push(format!("start_lives is negative: {}.", self.start_lives));
+        }
+        if self.start_jumps < 0 {
+            problems.push(format!("start_jumps is negative: {}.", self.start_jumps));
+        }
+        if self.player_speed <= 0 {
+            problems.push(format!(
+                "player_speed should be positive, not {}.",
+                self.player_speed
+            ));
+        }
+        if self.enemy_starting_speed <= 0 {
+            problems.push(format!(
+                "enemy_starting_speed should be positive, not {}.",
+                self.enemy_starting_speed
+            ));
+        }
+        problems
+    }
 }
 
 impl toybox_core::State for State {
@@ -1116,6 +1279,7 @@ impl toybox_core::State for State {
         self.state.score
     }
     fn update_mut(&mut self, buttons: Input) {
+        self.state.events.next_frame();
         let pre_update_score: i32 = self.score();
         let history_limit = self.config.history_limit;
 
@@ -1125,7 +1289,7 @@ impl toybox_core::State for State {
             &mut self.state.board,
             None,
             history_limit,
-            &mut self.state.rand,
+            self.state.rand.stream("player"),
         ) {
             // Don't award score for the first, semi-painted segment on a default Amidar board, but do paint it.
             let mut allow_score_change = true;
@@ -1145,8 +1309,10 @@ impl toybox_core::State for State {
 
             if score_change.triggers_chase {
                 self.state.chase_timer = self.config.chase_time;
+                self.state.events.push("chase_started", json!({}));
             }
         }
+        self.state.events.append(&mut self.state.board.events);
 
         if self.state.chase_timer > 0 {
             self.state.chase_timer -= 1;
@@ -1156,6 +1322,9 @@ impl toybox_core::State for State {
         } else if (buttons.button1 || buttons.button2) && self.state.jumps > 0 {
             self.state.jump_timer = self.config.jump_time;
             self.state.jumps -= 1;
+            self.state
+                .events
+                .push("jump", json!({ "jumps_left": self.state.jumps }));
         }
 
         let mut dead = false;
@@ -1170,13 +1339,13 @@ impl toybox_core::State for State {
         }
 
         // move enemies:
-        for e in self.state.enemies.iter_mut() {
+        for (i, e) in self.state.enemies.iter_mut().enumerate() {
             e.update(
                 Input::default(),
                 &mut self.state.board,
                 Some(self.state.player.clone()),
                 history_limit,
-                &mut self.state.rand,
+                self.state.rand.stream_indexed("enemy", i as u64),
             );
         }
 
@@ -1202,6 +1371,9 @@ impl toybox_core::State for State {
                     if !self.state.enemies[eid].caught {
                         self.state.score += self.config.chase_score_bonus;
                         self.state.enemies[eid].caught = true;
+                        self.state
+                            .events
+                            .push("enemy_caught", json!({ "enemy": eid }));
                     }
                 }
             }
@@ -1250,6 +1422,73 @@ impl toybox_core::State for State {
         }
     }
 
+    fn step(&mut self, buttons: Input) -> StepResult {
+        let (score, lives) = (self.score(), self.lives());
+        let level = self.state.level;
+        self.update_mut(buttons);
+
+        let mut result = StepResult::diff(score, lives, self);
+        result.level_complete = self.state.level > level;
+        result.events = self.state.events.drain();
+        result
+    }
+
+    fn drain_events(&mut self) -> Vec<Event> {
+        self.state.events.drain()
+    }
+
+    fn objects(&self) -> Vec<Object> {
+        let mut output = Vec::new();
+        if self.state.lives < 0 {
+            return output;
+        }
+        let (offset_x, offset_y) = screen::BOARD_OFFSET;
+
+        let (player_x, player_y) = self.state.player.position.to_screen().pixels();
+        let (player_w, player_h) = screen::PLAYER_SIZE;
+        output.push(
+            Object::new(
+                "player",
+                offset_x + player_x - 1,
+                offset_y + player_y - 1,
+                player_w,
+                player_h,
+            )
+            .with_attributes(json!({ "jumping": self.state.jump_timer > 0 })),
+        );
+
+        for (id, enemy) in self.state.enemies.iter().enumerate() {
+            let (x, y) = enemy.position.to_screen().pixels();
+            let (w, h) = screen::ENEMY_SIZE;
+            output.push(
+                Object::new("enemy", offset_x + x - 1, offset_y + y - 1, w, h).with_attributes(
+                    json!({ "id": id, "caught": enemy.caught, "chased": self.state.chase_timer > 0 }),
+                ),
+            );
+        }
+
+        // Boxes cover the area inside their painted border, as draw fills them.
+        for inner_box in self.state.board.boxes.iter() {
+            let origin = inner_box.top_left.translate(1, 1).to_world().to_screen();
+            let dest = inner_box.bottom_right.to_world().to_screen();
+            output.push(
+                Object::new(
+                    "box",
+                    offset_x + origin.sx,
+                    offset_y + origin.sy,
+                    dest.sx - origin.sx,
+                    dest.sy - origin.sy,
+                )
+                .with_attributes(json!({
+                    "painted": inner_box.painted,
+                    "triggers_chase": inner_box.triggers_chase,
+                })),
+            );
+        }
+
+        output
+    }
+
     fn draw(&self) -> Vec<Drawable> {
         let mut output = Vec::new();
         output.push(Drawable::Clear(self.config.bg_color));
@@ -1310,7 +1549,11 @@ impl toybox_core::State for State {
             }
         }
 
-        for inner_box in self.state.board.boxes.iter().filter(|b| b.painted) {
+        for (i, inner_box) in self.state.board.boxes.iter().enumerate() {
+            if !inner_box.painted {
+                continue;
+            }
+            let instance = i as i32 + 1;
             if self.config.render_images {
                 let top_left_in = inner_box.top_left.translate(1, 1);
                 let x1 = top_left_in.tx;
@@ -1322,11 +1565,14 @@ impl toybox_core::State for State {
                 for x in x1..x2 {
                     for y in y1..y2 {
                         let pt = TilePoint::new(x, y).to_world().to_screen();
-                        output.push(Drawable::sprite(
-                            pt.sx + offset_x,
-                            pt.sy + offset_y,
-                            images::PAINTED_BOX_BAR.clone(),
-                        ));
+                        output.push(
+                            Drawable::sprite(
+                                pt.sx + offset_x,
+                                pt.sy + offset_y,
+                                images::PAINTED_BOX_BAR.clone(),
+                            )
+                            .tagged(kinds::BOX, instance),
+                        );
                     }
                 }
             } else {
@@ -1334,13 +1580,16 @@ impl toybox_core::State for State {
                 let dest = inner_box.bottom_right.to_world().to_screen();
                 let w = dest.sx - origin.sx;
                 let h = dest.sy - origin.sy;
-                output.push(Drawable::rect(
-                    self.config.inner_painted_color,
-                    offset_x + origin.sx,
-                    offset_y + origin.sy,
-                    w,
-                    h,
-                ));
+                output.push(
+                    Drawable::rect(
+                        self.config.inner_painted_color,
+                        offset_x + origin.sx,
+                        offset_y + origin.sy,
+                        w,
+                        h,
+                    )
+                    .tagged(kinds::BOX, instance),
+                );
             }
         }
 
@@ -1352,27 +1601,33 @@ impl toybox_core::State for State {
             _ => unreachable!(),
         };
         if self.config.render_images {
-            output.push(Drawable::sprite(
-                offset_x + player_x - 1,
-                offset_y + player_y - 1,
-                player_sprite,
-            ))
+            output.push(
+                Drawable::sprite(
+                    offset_x + player_x - 1,
+                    offset_y + player_y - 1,
+                    player_sprite,
+                )
+                .tagged(kinds::PLAYER, 1),
+            )
         } else {
-            output.push(Drawable::rect(
-                self.config.player_color,
-                offset_x + player_x - 1,
-                offset_y + player_y - 1,
-                player_w,
-                player_h,
-            ));
+            output.push(
+                Drawable::rect(
+                    self.config.player_color,
+                    offset_x + player_x - 1,
+                    offset_y + player_y - 1,
+                    player_w,
+                    player_h,
+                )
+                .tagged(kinds::PLAYER, 1),
+            );
         }
 
-        for enemy in &self.state.enemies {
+        for (i, enemy) in self.state.enemies.iter().enumerate() {
             let (x, y) = enemy.position.to_screen().pixels();
             let (w, h) = screen::ENEMY_SIZE;
 
-            if self.config.render_images {
-                output.push(Drawable::sprite(
+            let drawable = if self.config.render_images {
+                Drawable::sprite(
                     offset_x + x - 1,
                     offset_y + y - 1,
                     if self.state.chase_timer > 0 {
@@ -1402,16 +1657,17 @@ impl toybox_core::State for State {
                             _ => unreachable!(),
                         }
                     },
-                ))
+                )
             } else {
-                output.push(Drawable::rect(
+                Drawable::rect(
                     self.config.enemy_color,
                     offset_x + x - 1,
                     offset_y + y - 1,
                     w,
                     h,
-                ));
-            }
+                )
+            };
+            output.push(drawable.tagged(kinds::ENEMY, i as i32 + 1));
         }
 
         output.extend(draw_score(
@@ -1432,10 +1688,62 @@ impl toybox_core::State for State {
         output
     }
 
+    /// The tile grid (' ' empty, '.' unpainted, '+' chase marker, '#' painted) with 'P' for the player and 'E' for enemies ('e' once caught).
+    fn to_text(&self) -> String {
+        let state = &self.state;
+        let mut grid: Vec<Vec<char>> = state
+            .board
+            .tiles
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|tile| match tile {
+                        Tile::Empty => ' ',
+                        Tile::Unpainted => '.',
+                        Tile::ChaseMarker => '+',
+                        Tile::Painted => '#',
+                    })
+                    .collect()
+            })
+            .collect();
+        {
+            let mut place = |mob: &Mob, ch: char| {
+                let tile = mob.position.to_tile();
+                if let Some(row) = grid.get_mut(tile.ty as usize) {
+                    if let Some(cell) = row.get_mut(tile.tx as usize) {
+                        *cell = ch;
+                    }
+                }
+            };
+            for enemy in &state.enemies {
+                place(enemy, if enemy.caught { 'e' } else { 'E' });
+            }
+            place(&state.player, 'P');
+        }
+
+        let mut output = format!(
+            "score: {} lives: {} jumps: {} level: {}\n",
+            state.score, state.lives, state.jumps, state.level
+        );
+        for row in grid {
+            output.extend(row);
+            output.push('\n');
+        }
+        output
+    }
+
     fn to_json(&self) -> String {
         serde_json::to_string(&self.state).expect("Should be no JSON Serialization Errors.")
     }
 
+    fn clone_box(&self) -> Box<toybox_core::State> {
+        Box::new(self.clone())
+    }
+
+    fn state_hash(&self) -> u64 {
+        toybox_core::stable_hash(&self.state)
+    }
+
     fn query_json(&self, query: &str, args: &serde_json::Value) -> Result<String, QueryError> {
         let state = &self.state;
         Ok(match query {
@@ -1505,6 +1813,7 @@ impl toybox_core::State for State {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashSet;
     use toybox_core::State;
 
     #[test]
@@ -1581,6 +1890,39 @@ mod tests {
         println!("{}", serde_json::to_string_pretty(&data).unwrap());
     }
 
+    #[test]
+    fn test_from_json_bad_board() {
+        use toybox_core::Simulation;
+        let mut config = Amidar::default();
+        config.board[0] = config.board[0].replace("=", "?");
+        let json = serde_json::to_string(&config).unwrap();
+        match Amidar::default().from_json(&json) {
+            Err(LoadError::InvalidConfig(_)) => {}
+            Err(e) => panic!("Unexpected error: {}", e),
+            Ok(_) => panic!("A board with unknown tiles should not load."),
+        }
+        match Amidar::default().from_json("{") {
+            Err(LoadError::ParseError(_)) => {}
+            Err(e) => panic!("Unexpected error: {}", e),
+            Ok(_) => panic!("Truncated JSON should not load."),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        use toybox_core::Simulation;
+        assert!(Amidar::default().validate().is_empty());
+
+        let mut config = Amidar::default();
+        // The middle of the default board is not track.
+        config.player_start = TilePoint::new(2, 2);
+        config.enemies.push(MovementAI::EnemyLookupAI {
+            next: 0,
+            default_route_index: 99,
+        });
+        assert_eq!(config.validate().len(), 2);
+    }
+
     fn player_tile(state: &State) -> (i32, i32) {
         serde_json::from_str(
             &state
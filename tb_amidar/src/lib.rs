@@ -1,4 +1,5 @@
 extern crate serde;
+#[macro_use]
 extern crate serde_json;
 extern crate toybox_core;
 #[macro_use]
@@ -2,8 +2,9 @@ use serde::{Deserialize, Serialize};
 use toybox_core::graphics::Color;
 use toybox_core::random;
 use toybox_core::Direction;
+use toybox_core::EventLog;
 
-use std::collections::{HashSet, VecDeque};
+use std::collections::{BTreeSet, VecDeque};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Amidar {
@@ -116,9 +117,12 @@ pub struct Board {
     pub tiles: Vec<Vec<Tile>>,
     pub width: u32,
     pub height: u32,
-    pub junctions: HashSet<u32>,
-    pub chase_junctions: HashSet<u32>,
+    pub junctions: BTreeSet<u32>,
+    pub chase_junctions: BTreeSet<u32>,
     pub boxes: Vec<GridBox>,
+    /// Boxes painted since the State last collected them; not saved in JSON.
+    #[serde(skip)]
+    pub events: EventLog,
 }
 
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
@@ -132,7 +136,8 @@ pub struct BoardUpdate {
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct StateCore {
-    pub rand: random::Gen,
+    /// Random streams: "player" and one "enemy" stream per enemy, for movement.
+    pub rand: random::Streams,
     pub score: i32,
     pub lives: i32,
     pub jumps: i32,
@@ -142,8 +147,12 @@ pub struct StateCore {
     pub enemies: Vec<Mob>,
     pub board: Board,
     pub level: i32,
+    /// Box completions, chase triggers and catches since the last State::drain_events; not saved in JSON.
+    #[serde(skip)]
+    pub events: EventLog,
 }
 
+#[derive(Clone)]
 pub struct State {
     pub config: Amidar,
     pub state: StateCore,
@@ -3,9 +3,13 @@ use super::font::{draw_lives, draw_score, DIGIT_WIDTH};
 use super::vec2d::Vec2D;
 use ordered_float::NotNan;
 use toybox_core;
-use toybox_core::graphics::{Color, Drawable};
+use toybox_core::collision::{Aabb, Circle, Contact};
+use toybox_core::graphics::{unique_colors, Color, Drawable};
 use toybox_core::random;
-use toybox_core::{AleAction, Input, QueryError};
+use toybox_core::{
+    Action, ActionSpace, AleAction, Event, EventLog, Input, LoadError, Object, QueryError,
+    StepResult,
+};
 
 use serde_json;
 
@@ -78,6 +82,13 @@ mod screen {
     pub const BALL_ANGLE_RANGE: f64 = 120.0;
 }
 
+mod kinds {
+    pub const NAMES: &[&str] = &["paddle", "ball", "brick"];
+    pub const PADDLE: u8 = 1;
+    pub const BALL: u8 = 2;
+    pub const BRICK: u8 = 3;
+}
+
 impl Breakout {
     #[cfg(test)]
     fn unique_colors(&self) -> Vec<&Color> {
@@ -123,6 +134,7 @@ impl Default for Breakout {
                 StartBall::new(0.9 * w, y, 150.0),
             ],
             paddle_discrete_segments: Some(5),
+            swept_collisions: false,
         }
     }
 }
@@ -159,11 +171,12 @@ impl Brick {
         }
     }
 
+    pub fn aabb(&self) -> Aabb {
+        Aabb::new(self.position.x, self.position.y, self.size.x, self.size.y)
+    }
+
     pub fn contains(&self, point: &Vec2D) -> bool {
-        point.x >= self.position.x
-            && point.x <= (self.position.x + self.size.x)
-            && point.y >= self.position.y
-            && point.y <= (self.position.y + self.size.y)
+        self.aabb().contains(point.x, point.y)
     }
 }
 
@@ -171,6 +184,9 @@ impl toybox_core::Simulation for Breakout {
     fn reset_seed(&mut self, seed: u32) {
         self.rand.reset_seed(seed);
     }
+    fn reset_seed_u64(&mut self, seed: u64) {
+        self.rand.reset_seed_u64(seed);
+    }
     fn game_size(&self) -> (i32, i32) {
         screen::GAME_SIZE
     }
@@ -188,6 +204,48 @@ impl toybox_core::Simulation for Breakout {
         actions
     }
 
+    /// Breakout can also be played with a paddle controller, which puts the paddle directly where it points.
+    /// The fire button serves a new ball, so it does nothing while one is in play.
+    fn action_space(&self) -> ActionSpace {
+        let actions = self
+            .legal_action_set()
+            .into_iter()
+            .map(|a| {
+                let action = Action::from_ale(a);
+                let action = if action.input.button1 {
+                    action.noop_when("ball_in_play")
+                } else {
+                    action
+                };
+                match a {
+                    AleAction::FIRE => action.with_name("serve"),
+                    AleAction::LEFT | AleAction::RIGHT => action.noop_when("paddle_controller"),
+                    _ => action,
+                }
+            })
+            .collect();
+        ActionSpace::new(actions).with_paddle()
+    }
+
+    fn object_kinds(&self) -> Vec<String> {
+        kinds::NAMES.iter().map(|k| k.to_string()).collect()
+    }
+
+    fn palette(&self) -> Vec<Color> {
+        let mut colors = vec![
+            self.bg_color,
+            self.frame_color,
+            self.paddle_color,
+            self.ball_color,
+        ];
+        colors.extend(self.row_colors.iter().cloned());
+        // The frame's colored spots and the score digits have fixed colors.
+        colors.push((&screen::FRAME_LEFT_SUPPORT_COLOR).into());
+        colors.push((&screen::FRAME_RIGHT_SUPPORT_COLOR).into());
+        colors.push((&screen::FRAME_COLOR).into());
+        unique_colors(colors)
+    }
+
     /// Create a new game of breakout.
     fn new_game(&mut self) -> Box<toybox_core::State> {
         let mut bricks = Vec::new();
@@ -233,9 +291,10 @@ impl toybox_core::Simulation for Breakout {
                 ball_radius: 2.0,
                 paddle_width: screen::PADDLE_START_SIZE.0.into(),
                 paddle_speed: 4.0,
-                rand: random::Gen::new_child(&mut self.rand),
+                rand: random::Streams::new(random::Gen::new_child(&mut self.rand)),
                 bricks,
                 reset: true,
+                events: EventLog::default(),
             },
         };
 
@@ -243,10 +302,7 @@ impl toybox_core::Simulation for Breakout {
         Box::new(state)
     }
 
-    fn new_state_from_json(
-        &self,
-        json_str: &str,
-    ) -> Result<Box<toybox_core::State>, serde_json::Error> {
+    fn new_state_from_json(&self, json_str: &str) -> Result<Box<toybox_core::State>, LoadError> {
         let state: StateCore = serde_json::from_str(json_str)?;
         Ok(Box::new(State {
             config: self.clone(),
@@ -254,14 +310,81 @@ impl toybox_core::Simulation for Breakout {
         }))
     }
 
-    fn from_json(&self, json_str: &str) -> Result<Box<toybox_core::Simulation>, serde_json::Error> {
+    fn from_json(&self, json_str: &str) -> Result<Box<toybox_core::Simulation>, LoadError> {
         let config: Breakout = serde_json::from_str(json_str)?;
+        // new_game looks up a score for every row of bricks.
+        if config.row_scores.len() < config.row_colors.len() {
+            return Err(LoadError::InvalidConfig(format!(
+                "row_scores has {} entries but row_colors has {}.",
+                config.row_scores.len(),
+                config.row_colors.len()
+            )));
+        }
         Ok(Box::new(config))
     }
 
     fn to_json(&self) -> String {
         serde_json::to_string(self).expect("Breakout should be JSON-serializable!")
     }
+
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.row_scores.len() < self.row_colors.len() {
+            problems.push(format!(
+                "row_scores has {} entries but row_colors has {}.",
+                self.row_scores.len(),
+                self.row_colors.len()
+            ));
+        }
+        let bricks_bottom = screen::BOARD_TOP_Y
+            + screen::ROOF_SPACING
+            + (self.row_colors.len() as i32) * screen::BRICK_HEIGHT;
+        if bricks_bottom >= screen::PADDLE_START_Y {
+            problems.push(format!(
+                "{} rows of bricks reach past the paddle.",
+                self.row_colors.len()
+            ));
+        }
+        if self.start_lives < 0 {
+            problems.push(format!("start_lives is negative: {}.", self.start_lives));
+        }
+        if self.ball_speed_slow.is_nan() || self.ball_speed_slow <= 0.0 {
+            problems.push(format!(
+                "ball_speed_slow should be positive, not {}.",
+                self.ball_speed_slow
+            ));
+        }
+        if self.ball_speed_fast.is_nan() || self.ball_speed_fast <= 0.0 {
+            problems.push(format!(
+                "ball_speed_fast should be positive, not {}.",
+                self.ball_speed_fast
+            ));
+        }
+        if let Some(segments) = self.paddle_discrete_segments {
+            if segments <= 0 {
+                problems.push(format!(
+                    "paddle_discrete_segments should be positive, not {}.",
+                    segments
+                ));
+            }
+        }
+        if self.ball_start_positions.is_empty() {
+            problems.push("ball_start_positions is empty; no ball can be served.".to_owned());
+        }
+        let (left, right) = (screen::BOARD_LEFT_X as f64, screen::BOARD_RIGHT_X as f64);
+        let (top, bottom) = (screen::BOARD_TOP_Y as f64, screen::BOARD_BOTTOM_Y as f64);
+        for (i, start) in self.ball_start_positions.iter().enumerate() {
+            let on_board =
+                start.x >= left && start.x <= right && start.y >= top && start.y <= bottom;
+            if !on_board {
+                problems.push(format!(
+                    "ball_start_positions[{}] at ({}, {}) is outside the board.",
+                    i, start.x, start.y
+                ));
+            }
+        }
+        problems
+    }
 }
 
 impl State {
@@ -269,7 +392,7 @@ impl State {
         let option: &StartBall = self
             .config
             .ball_start_positions
-            .choose(&mut self.state.rand)
+            .choose(self.state.rand.stream("ball_start"))
             .unwrap();
 
         let mut ball = Body2D::new_pos(option.x, option.y);
@@ -283,7 +406,13 @@ impl State {
         let left = buttons.left;
         let right = buttons.right;
 
-        if left {
+        if let Some(position) = buttons.paddle {
+            // A paddle controller places the paddle directly, anywhere it could slide to.
+            let (min_x, max_x) = self.paddle_range();
+            let position = f64::from(position.max(0.0).min(1.0));
+            self.state.paddle.position.x = min_x + position * (max_x - min_x);
+            self.state.paddle.velocity.x = 0.0;
+        } else if left {
             self.state.paddle.velocity.x = -self.state.paddle_speed;
         } else if right {
             self.state.paddle.velocity.x = self.state.paddle_speed;
@@ -291,9 +420,15 @@ impl State {
             self.state.paddle.velocity.x = 0.0;
         }
     }
+    /// The leftmost and rightmost x that the paddle's center can reach.
+    fn paddle_range(&self) -> (f64, f64) {
+        (
+            screen::BOARD_LEFT_X as f64 - self.state.paddle_width / 2.0,
+            screen::BOARD_RIGHT_X as f64 + self.state.paddle_width / 2.0,
+        )
+    }
     fn keep_paddle_on_screen(&mut self) {
-        let left = screen::BOARD_LEFT_X as f64 - self.state.paddle_width / 2.0;
-        let right = screen::BOARD_RIGHT_X as f64 + self.state.paddle_width / 2.0;
+        let (left, right) = self.paddle_range();
         if self.state.paddle.position.x < left {
             self.state.paddle.position.x = left;
             self.state.paddle.velocity.x = 0.0;
@@ -302,53 +437,196 @@ impl State {
             self.state.paddle.velocity.x = 0.0;
         }
     }
+    /// The paddle is a flat line along its top.
+    fn paddle_aabb(&self) -> Aabb {
+        Aabb::from_center(
+            self.state.paddle.position.x,
+            self.state.paddle.position.y,
+            self.state.paddle_width,
+            0.0,
+        )
+    }
+    /// Send a ball off the paddle at an angle that depends on where along the paddle it landed.
+    fn bounce_paddle(&mut self, ball_index: usize) {
+        let paddle_x = self.state.paddle.position.x;
+        let paddle_width = self.state.paddle_width;
+        let ball = &mut self.state.balls[ball_index];
+
+        // get x location of ball hit relative to paddle
+        let ball_hit_x = ball.position.x - (paddle_x - (paddle_width / 2.0));
+        // get normalized location of ball hit along paddle
+        let mut paddle_normalized_relative_intersect_x = 1.0 - ball_hit_x / paddle_width;
+
+        // If we have discrete segments, discretize that.
+        if let Some(segments) = self.config.paddle_discrete_segments {
+            // Multiply to get an integer segment id.
+            let segment_id =
+                (paddle_normalized_relative_intersect_x * segments as f64).floor() as i32;
+            // Center within segments.
+            let shift = 1.0 / (2.0 * segments as f64);
+            // Divide to go back to a number from 0..1.0
+            let relative = (segment_id as f64) / (segments as f64) + shift;
+            // Overwrite continuous value.
+            paddle_normalized_relative_intersect_x = relative;
+        }
+
+        // convert this normalized parameter to the degree of the bounce angle
+        let bounce_angle = paddle_normalized_relative_intersect_x * screen::BALL_ANGLE_RANGE
+            + screen::BALL_ANGLE_MIN;
+
+        ball.velocity = Vec2D::from_polar(ball.velocity.magnitude(), bounce_angle.to_radians());
+        // calculations use non-graphics polar orientation
+        // to quickly fix, we reflect over the x-axis
+        ball.velocity.y *= -1.0;
+
+        self.state.events.push(
+            "paddle_bounce",
+            json!({ "x": ball.position.x, "angle": bounce_angle }),
+        );
+    }
+    /// Bounce a ball off a brick it touched, scoring it if it breaks.
+    fn bounce_brick(&mut self, ball_index: usize, brick_index: usize, normal: (f64, f64)) {
+        let ball = &mut self.state.balls[ball_index];
+        let brick = &mut self.state.bricks[brick_index];
+
+        // Reflect whichever directions carry the ball into the brick; a corner can reflect both.
+        if normal.0 * ball.velocity.x < 0.0 {
+            ball.velocity.x *= -1.0;
+        }
+        if normal.1 * ball.velocity.y < 0.0 {
+            ball.velocity.y *= -1.0;
+        }
+        if brick.destructible {
+            brick.alive = false;
+            self.state.points += brick.points;
+        }
+        self.state.events.push(
+            "brick_hit",
+            json!({ "row": brick.row, "col": brick.col, "destroyed": !brick.alive }),
+        );
+        if brick.depth >= self.config.ball_speed_row_depth {
+            // Potentially speed up the ball. This will be a no-op if it's already fast.
+            let theta = ball.velocity.angle();
+            ball.velocity = Vec2D::from_polar(self.config.ball_speed_fast, theta);
+        }
+    }
+    /// Bounce a ball off any paddle it overlaps after moving, if it's going downwards.
     fn check_bounce_paddle(&mut self) {
         let radius = self.state.ball_radius;
-
-        for ball in self.state.balls.iter_mut() {
-            // Only check balls going downwards.
-            if ball.velocity.y <= 0.0 {
-                continue;
+        let paddle = self.paddle_aabb();
+
+        for i in 0..self.state.balls.len() {
+            let hit = {
+                let ball = &self.state.balls[i];
+                // check paddle, a flat line, against the ball's bounding box:
+                let ball_box =
+                    Aabb::from_center(ball.position.x, ball.position.y, 2.0 * radius, 2.0 * radius);
+                ball.velocity.y > 0.0 && ball_box.intersects(&paddle)
+            };
+            if hit {
+                self.bounce_paddle(i);
             }
+        }
+    }
+    /// Bounce a ball off the first live brick that the edge of the ball it's moving towards has entered.
+    fn check_bounce_bricks(&mut self, ball_index: usize) {
+        let hit = {
+            let radius = self.state.ball_radius;
+            let ball = &self.state.balls[ball_index];
+            let ball_bounce_y = Vec2D::new(
+                ball.position.x,
+                ball.position.y + ball.velocity.y.signum() * radius,
+            );
+            let ball_bounce_x = Vec2D::new(
+                ball.position.x + ball.velocity.x.signum() * radius,
+                ball.position.y,
+            );
+            // The normal faces back against the motion along whichever axis entered the brick.
+            let normal_x = (-ball.velocity.x.signum(), 0.0);
+            let normal_y = (0.0, -ball.velocity.y.signum());
 
-            // check paddle:
-            let paddle_ball_same_x = (ball.position.x - self.state.paddle.position.x).abs()
-                < radius + self.state.paddle_width / 2.0;
-            let paddle_ball_same_y =
-                (self.state.paddle.position.y - ball.position.y).abs() < radius;
-
-            if paddle_ball_same_x && paddle_ball_same_y {
-                // get x location of ball hit relative to paddle
-                let ball_hit_x = ball.position.x
-                    - (self.state.paddle.position.x - (self.state.paddle_width / 2.0));
-                // get normalized location of ball hit along paddle
-                let mut paddle_normalized_relative_intersect_x =
-                    1.0 - ball_hit_x / self.state.paddle_width;
-
-                // If we have discrete segments, discretize that.
-                if let Some(segments) = self.config.paddle_discrete_segments {
-                    // Multiply to get an integer segment id.
-                    let segment_id =
-                        (paddle_normalized_relative_intersect_x * segments as f64).floor() as i32;
-                    // Center within segments.
-                    let shift = 1.0 / (2.0 * segments as f64);
-                    // Divide to go back to a number from 0..1.0
-                    let relative = (segment_id as f64) / (segments as f64) + shift;
-                    // Overwrite continuous value.
-                    paddle_normalized_relative_intersect_x = relative;
+            self.state
+                .bricks
+                .iter()
+                .enumerate()
+                .filter(|&(_, b)| b.alive)
+                .filter_map(|(j, brick)| {
+                    if brick.contains(&ball_bounce_x) {
+                        Some((j, normal_x))
+                    } else if brick.contains(&ball_bounce_y) {
+                        Some((j, normal_y))
+                    } else {
+                        None
+                    }
+                })
+                .next()
+        };
+        if let Some((brick_index, normal)) = hit {
+            self.bounce_brick(ball_index, brick_index, normal);
+        }
+    }
+    /// Move each ball for time_step, stopping at the first brick or paddle it touches to bounce off it.
+    fn move_balls(&mut self, time_step: f64) {
+        let radius = self.state.ball_radius;
+        let paddle = self.paddle_aabb();
+
+        for i in 0..self.state.balls.len() {
+            let (position, velocity) = {
+                let ball = &self.state.balls[i];
+                (ball.position.clone(), ball.velocity.clone())
+            };
+            let circle = Circle::new(position.x, position.y, radius);
+            let (dx, dy) = (velocity.x * time_step, velocity.y * time_step);
+            // Something the ball is already inside only counts if the ball is heading into it; otherwise it is leaving.
+            let heading_into = |target: &Aabb, contact: &Contact| {
+                let (cx, cy) = target.center();
+                contact.time > 0.0 || (cx - position.x) * dx + (cy - position.y) * dy > 0.0
+            };
+
+            // Only balls going downwards can bounce off the paddle.
+            let mut first: Option<(Contact, Option<usize>)> = None;
+            if velocity.y > 0.0 {
+                if let Some(contact) = circle.sweep(dx, dy, &paddle) {
+                    first = Some((contact, None));
                 }
+            }
+            for (j, brick) in self
+                .state
+                .bricks
+                .iter()
+                .enumerate()
+                .filter(|&(_, b)| b.alive)
+            {
+                let target = brick.aabb();
+                if let Some(contact) = circle.sweep(dx, dy, &target) {
+                    let earlier = first.as_ref().map_or(true, |(f, _)| contact.time < f.time);
+                    if earlier && heading_into(&target, &contact) {
+                        first = Some((contact, Some(j)));
+                    }
+                }
+            }
 
-                // convert this normalized parameter to the degree of the bounce angle
-                let bounce_angle = paddle_normalized_relative_intersect_x
-                    * screen::BALL_ANGLE_RANGE
-                    + screen::BALL_ANGLE_MIN;
-
-                ball.velocity =
-                    Vec2D::from_polar(ball.velocity.magnitude(), bounce_angle.to_radians());
-                // calculations use non-graphics polar orientation
-                // to quickly fix, we reflect over the x-axis
-                ball.velocity.y *= -1.0;
+            let (contact, hit) = match first {
+                Some(first) => first,
+                None => {
+                    self.state.balls[i].integrate_mut(time_step);
+                    continue;
+                }
+            };
+            self.state.balls[i].integrate_mut(contact.time * time_step);
+            match hit {
+                None => self.bounce_paddle(i),
+                Some(j) => {
+                    // Already inside a brick gives no normal; send the ball back the way it came vertically.
+                    let normal = if contact.normal == (0.0, 0.0) {
+                        (0.0, -velocity.y.signum())
+                    } else {
+                        contact.normal
+                    };
+                    self.bounce_brick(i, j, normal);
+                }
             }
+            self.state.balls[i].integrate_mut((1.0 - contact.time) * time_step);
         }
     }
 
@@ -376,13 +654,18 @@ impl State {
     fn update_time_slice(&mut self, time_step: f64) {
         // Update positions.
 
-        for ball in self.state.balls.iter_mut() {
-            ball.integrate_mut(time_step);
+        if self.config.swept_collisions {
+            self.state.paddle.integrate_mut(time_step);
+            self.keep_paddle_on_screen();
+            self.move_balls(time_step);
+        } else {
+            for ball in self.state.balls.iter_mut() {
+                ball.integrate_mut(time_step);
+            }
+            self.state.paddle.integrate_mut(time_step);
+            self.keep_paddle_on_screen();
+            self.check_bounce_paddle();
         }
-        self.state.paddle.integrate_mut(time_step);
-
-        self.keep_paddle_on_screen();
-        self.check_bounce_paddle();
 
         // check lose?
         if self.check_ball_death() && !self.state.is_dead {
@@ -394,51 +677,22 @@ impl State {
             return;
         }
 
-        for ball in self.state.balls.iter_mut() {
-            let radius = self.state.ball_radius;
-
+        let radius = self.state.ball_radius;
+        for i in 0..self.state.balls.len() {
             // Handle collisions:
-            if ball.velocity.y < 0.0 {
+            if self.state.balls[i].velocity.y < 0.0 {
                 // bounce ceiling?
-                if ball.position.y - radius < screen::BOARD_TOP_Y.into() {
-                    ball.velocity.y *= -1.0;
+                if self.state.balls[i].position.y - radius < screen::BOARD_TOP_Y.into() {
+                    self.state.balls[i].velocity.y *= -1.0;
                     self.state.paddle_width = screen::PADDLE_SMALL_SIZE.0.into();
                 }
             }
 
-            // check living bricks:
-            let ball_bounce_y = Vec2D::new(
-                ball.position.x,
-                ball.position.y + ball.velocity.y.signum() * radius,
-            );
-            let ball_bounce_x = Vec2D::new(
-                ball.position.x + ball.velocity.x.signum() * radius,
-                ball.position.y,
-            );
-
-            for brick in self.state.bricks.iter_mut().filter(|b| b.alive) {
-                let mut hit = false;
-                if brick.contains(&ball_bounce_x) {
-                    hit = true;
-                    ball.velocity.x *= -1.0;
-                } else if brick.contains(&ball_bounce_y) {
-                    hit = true;
-                    ball.velocity.y *= -1.0;
-                }
-                if hit {
-                    if brick.destructible {
-                        brick.alive = false;
-                        self.state.points += brick.points;
-                    }
-                    if brick.depth >= self.config.ball_speed_row_depth {
-                        // Potentially speed up the ball. This will be a no-op if it's already fast.
-                        let theta = ball.velocity.angle();
-                        ball.velocity = Vec2D::from_polar(self.config.ball_speed_fast, theta);
-                    }
-                    break;
-                }
+            if !self.config.swept_collisions {
+                self.check_bounce_bricks(i);
             }
 
+            let ball = &mut self.state.balls[i];
             // bounce right wall?
             if ball.velocity.x > 0.0 {
                 if ball.position.x + radius > screen::BOARD_RIGHT_X.into() {
@@ -464,6 +718,7 @@ impl toybox_core::State for State {
 
     /// Mutably update the game state.
     fn update_mut(&mut self, buttons: Input) {
+        self.state.events.next_frame();
         self.update_paddle_movement(buttons);
 
         if self.state.is_dead {
@@ -522,6 +777,77 @@ impl toybox_core::State for State {
         }
     }
 
+    fn step(&mut self, buttons: Input) -> StepResult {
+        let (score, lives) = (self.score(), self.lives());
+        let alive_before: Vec<bool> = self.state.bricks.iter().map(|b| b.alive).collect();
+        self.update_mut(buttons);
+
+        let mut result = StepResult::diff(score, lives, self);
+        // When the last brick goes, every brick comes back at once.
+        result.level_complete = self
+            .state
+            .bricks
+            .iter()
+            .zip(alive_before.iter())
+            .any(|(b, &was_alive)| b.alive && !was_alive);
+        result.events = self.state.events.drain();
+        result
+    }
+
+    fn drain_events(&mut self) -> Vec<Event> {
+        self.state.events.drain()
+    }
+
+    fn objects(&self) -> Vec<Object> {
+        let mut output = Vec::new();
+        if self.state.lives < 0 {
+            return output;
+        }
+
+        // Same boxes as draw uses.
+        let (paddle_x, paddle_y) = self.state.paddle.position.pixels();
+        let paddle_w = self.state.paddle_width as i32;
+        output.push(
+            Object::new(
+                "paddle",
+                paddle_x - paddle_w / 2,
+                paddle_y,
+                paddle_w,
+                screen::PADDLE_START_SIZE.1,
+            )
+            .with_velocity(self.state.paddle.velocity.x, self.state.paddle.velocity.y),
+        );
+
+        let ball_r = self.state.ball_radius as i32;
+        for (id, ball) in self.state.balls.iter().enumerate() {
+            let (ball_x, ball_y) = ball.position.pixels();
+            output.push(
+                Object::new(
+                    "ball",
+                    ball_x - ball_r,
+                    ball_y - ball_r,
+                    ball_r * 2,
+                    ball_r * 2,
+                )
+                .with_velocity(ball.velocity.x, ball.velocity.y)
+                .with_attributes(json!({ "id": id })),
+            );
+        }
+
+        for brick in self.state.bricks.iter().filter(|b| b.alive) {
+            let (x, y) = brick.position.pixels();
+            let (w, h) = brick.size.pixels();
+            output.push(Object::new("brick", x, y, w, h).with_attributes(json!({
+                "row": brick.row,
+                "col": brick.col,
+                "points": brick.points,
+                "destructible": brick.destructible,
+            })));
+        }
+
+        output
+    }
+
     fn draw(&self) -> Vec<Drawable> {
         let mut output = Vec::new();
         output.push(Drawable::Clear(self.config.bg_color));
@@ -575,34 +901,43 @@ impl toybox_core::State for State {
             return output;
         }
 
-        for brick in self.state.bricks.iter().filter(|b| b.alive) {
+        for (i, brick) in self.state.bricks.iter().enumerate() {
+            if !brick.alive {
+                continue;
+            }
             let (x, y) = brick.position.pixels();
             let (w, h) = brick.size.pixels();
 
-            output.push(Drawable::rect(brick.color, x, y, w, h));
+            output.push(Drawable::rect(brick.color, x, y, w, h).tagged(kinds::BRICK, i as i32 + 1));
         }
 
         let (paddle_x, paddle_y) = self.state.paddle.position.pixels();
         let paddle_w = self.state.paddle_width as i32;
 
-        output.push(Drawable::rect(
-            self.config.paddle_color,
-            paddle_x - paddle_w / 2,
-            paddle_y,
-            paddle_w,
-            screen::PADDLE_START_SIZE.1,
-        ));
+        output.push(
+            Drawable::rect(
+                self.config.paddle_color,
+                paddle_x - paddle_w / 2,
+                paddle_y,
+                paddle_w,
+                screen::PADDLE_START_SIZE.1,
+            )
+            .tagged(kinds::PADDLE, 1),
+        );
 
         let ball_r = self.state.ball_radius as i32;
-        for ball in self.state.balls.iter() {
+        for (i, ball) in self.state.balls.iter().enumerate() {
             let (ball_x, ball_y) = ball.position.pixels();
-            output.push(Drawable::rect(
-                self.config.ball_color,
-                ball_x - ball_r,
-                ball_y - ball_r,
-                ball_r * 2,
-                ball_r * 2,
-            ));
+            output.push(
+                Drawable::rect(
+                    self.config.ball_color,
+                    ball_x - ball_r,
+                    ball_y - ball_r,
+                    ball_r * 2,
+                    ball_r * 2,
+                )
+                .tagged(kinds::BALL, i as i32 + 1),
+            );
         }
 
         let score_offset = 88;
@@ -619,10 +954,52 @@ impl toybox_core::State for State {
         output
     }
 
+    /// Bricks by row and column ('#' alive, '=' indestructible, '.' gone), then the paddle and balls.
+    fn to_text(&self) -> String {
+        let state = &self.state;
+        let rows = state.bricks.iter().map(|b| b.row + 1).max().unwrap_or(0);
+        let cols = state.bricks.iter().map(|b| b.col + 1).max().unwrap_or(0);
+        let mut grid = vec![vec![' '; cols as usize]; rows as usize];
+        for brick in &state.bricks {
+            grid[brick.row as usize][brick.col as usize] = if !brick.alive {
+                '.'
+            } else if brick.destructible {
+                '#'
+            } else {
+                '='
+            };
+        }
+
+        let mut output = format!("score: {} lives: {}\n", state.points, state.lives);
+        for row in grid {
+            output.extend(row);
+            output.push('\n');
+        }
+        output.push_str(&format!(
+            "paddle: x={:.1} width={}\n",
+            state.paddle.position.x, state.paddle_width
+        ));
+        for ball in &state.balls {
+            output.push_str(&format!(
+                "ball: ({:.1}, {:.1}) moving ({:.2}, {:.2})\n",
+                ball.position.x, ball.position.y, ball.velocity.x, ball.velocity.y
+            ));
+        }
+        output
+    }
+
     fn to_json(&self) -> String {
         serde_json::to_string(&self.state).expect("Should be no JSON Serialization Errors.")
     }
 
+    fn clone_box(&self) -> Box<toybox_core::State> {
+        Box::new(self.clone())
+    }
+
+    fn state_hash(&self) -> u64 {
+        toybox_core::stable_hash(&self.state)
+    }
+
     fn query_json(&self, query: &str, args: &serde_json::Value) -> Result<String, QueryError> {
         let config = &self.config;
         let state = &self.state;
@@ -724,4 +1101,148 @@ mod tests {
             .unwrap();
         assert_eq!(empty, "[]");
     }
+
+    #[test]
+    fn test_validate() {
+        assert!(Breakout::default().validate().is_empty());
+
+        let mut config = Breakout::default();
+        config.row_scores.pop();
+        config
+            .ball_start_positions
+            .push(StartBall::new(-10.0, 80.0, 30.0));
+        assert_eq!(config.validate().len(), 2);
+    }
+
+    #[test]
+    fn test_events() {
+        let mut config = Breakout::default();
+        let json = config.new_game().to_json();
+        let mut state = State {
+            config,
+            state: serde_json::from_str(&json).unwrap(),
+        };
+
+        let mut kinds = HashSet::new();
+        for frame in 1..5000 {
+            // Serve, then keep the paddle under the ball.
+            let ball_x = state.state.balls.get(0).map(|b| b.position.x);
+            let paddle_x = state.state.paddle.position.x;
+            let buttons = Input {
+                button1: true,
+                left: ball_x.map(|x| x < paddle_x - 2.0).unwrap_or(false),
+                right: ball_x.map(|x| x > paddle_x + 2.0).unwrap_or(false),
+                ..Input::default()
+            };
+            toybox_core::State::update_mut(&mut state, buttons);
+            for event in toybox_core::State::drain_events(&mut state) {
+                assert_eq!(event.frame, frame);
+                kinds.insert(event.kind);
+            }
+            if kinds.len() == 2 {
+                break;
+            }
+        }
+        assert!(kinds.contains("paddle_bounce"));
+        assert!(kinds.contains("brick_hit"));
+    }
+
+    #[test]
+    fn test_swept_collisions() {
+        let mut config = Breakout::default();
+        assert!(!config.swept_collisions);
+        let json = config.new_game().to_json();
+        let mut state = State {
+            config,
+            state: serde_json::from_str(&json).unwrap(),
+        };
+        let radius = state.state.ball_radius;
+        // A brick in the bottom row, with its neighbours gone so that its corners are exposed.
+        // The top row stays, or else breaking this brick would clear the level.
+        let top = state.config.row_colors.len() as u32 - 1;
+        let index = state
+            .state
+            .bricks
+            .iter()
+            .position(|b| b.depth == 0 && b.col == 5)
+            .unwrap();
+        for (i, brick) in state.state.bricks.iter_mut().enumerate() {
+            brick.alive = i == index || brick.depth == top;
+        }
+        let target = state.state.bricks[index].aabb();
+        state.state.is_dead = false;
+
+        // Straight up, one pixel short of its bottom face.
+        let (x, _) = target.center();
+        let start_y = target.y2() + radius + 1.0;
+        // By default it only turns around once a whole 2-pixel sub-step has taken it inside.
+        state.state.balls = vec![Body2D::new_detailed(x, start_y, 0.0, -5.0)];
+        toybox_core::State::update_mut(&mut state, Input::default());
+        assert!(!state.state.bricks[index].alive);
+        assert!((state.state.balls[0].velocity.y - 5.0).abs() < 1e-9);
+        assert!((state.state.balls[0].position.y - (start_y + 1.0)).abs() < 1e-9);
+
+        // Swept, it turns around where it touches.
+        state.config.swept_collisions = true;
+        state.state.bricks[index].alive = true;
+        state.state.balls = vec![Body2D::new_detailed(x, start_y, 0.0, -5.0)];
+        toybox_core::State::update_mut(&mut state, Input::default());
+        assert!(!state.state.bricks[index].alive);
+        assert_eq!(state.state.balls[0].velocity.x, 0.0);
+        assert!((state.state.balls[0].velocity.y - 5.0).abs() < 1e-9);
+        assert!((state.state.balls[0].position.y - (start_y + 3.0)).abs() < 1e-9);
+
+        // Diagonally into its bottom-left corner: it comes back the way it came.
+        state.state.bricks[index].alive = true;
+        let start = (target.x - radius - 2.0, target.y2() + radius + 2.0);
+        state.state.balls = vec![Body2D::new_detailed(start.0, start.1, 3.0, -3.0)];
+        toybox_core::State::update_mut(&mut state, Input::default());
+        assert!(!state.state.bricks[index].alive);
+        assert!(state.state.balls[0].velocity.x < 0.0);
+        assert!(state.state.balls[0].velocity.y > 0.0);
+        assert!(!Circle::new(
+            state.state.balls[0].position.x,
+            state.state.balls[0].position.y,
+            radius
+        )
+        .intersects(&target));
+    }
+
+    #[test]
+    fn test_paddle_controller() {
+        let mut config = Breakout::default();
+        assert!(config.action_space().paddle);
+        let json = config.new_game().to_json();
+        let mut state = State {
+            config,
+            state: serde_json::from_str(&json).unwrap(),
+        };
+        let (min_x, max_x) = state.paddle_range();
+
+        // The paddle wins over the direction buttons, and is clamped to its range.
+        for &(position, expected) in &[(0.0, min_x), (1.0, max_x), (2.0, max_x), (-1.0, min_x)] {
+            let buttons = Input {
+                right: true,
+                ..Input::default()
+            }
+            .with_paddle(position);
+            toybox_core::State::update_mut(&mut state, buttons);
+            assert_eq!(state.state.paddle.position.x, expected);
+            assert_eq!(state.state.paddle.velocity.x, 0.0);
+        }
+        toybox_core::State::update_mut(&mut state, Input::default().with_paddle(0.5));
+        assert!((state.state.paddle.position.x - (min_x + max_x) / 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_from_json_short_row_scores() {
+        let mut config = Breakout::default();
+        config.row_scores.pop();
+        let json = serde_json::to_string(&config).unwrap();
+        match Breakout::default().from_json(&json) {
+            Err(LoadError::InvalidConfig(_)) => {}
+            Err(e) => panic!("Unexpected error: {}", e),
+            Ok(_) => panic!("Short row_scores should not load."),
+        }
+    }
 }
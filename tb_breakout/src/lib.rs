@@ -3,6 +3,7 @@
 //! None of the modules in this crate are public. The `Breakout` struct is the `toybox_core::Simulation` and the `State` struct is the `toybox_core::State` used generically by other crates.
 
 extern crate serde;
+#[macro_use]
 extern crate serde_json;
 extern crate toybox_core;
 #[macro_use]
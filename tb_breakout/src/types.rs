@@ -2,6 +2,7 @@ use super::body2d::Body2D;
 use super::vec2d::Vec2D;
 use toybox_core::graphics::Color;
 use toybox_core::random;
+use toybox_core::EventLog;
 
 /// Breakout is configured to sample randomly from ball starting positions. This struct contains all the information needed to add a new option.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -55,6 +56,10 @@ pub struct Breakout {
     pub ball_start_positions: Vec<StartBall>,
     /// When this is None, the paddle uses continuous logic for bouncing (imagining the paddle is kind of a circle). In the real game, some discrete math was used; i.e. the paddle behaves like a n-polygon. This could affect learning speed.
     pub paddle_discrete_segments: Option<i32>,
+    /// Bounce the ball where it first touches a brick or the paddle, found by sweeping it along its path, rather than once it overlaps one at the end of a sub-step.
+    /// Off by default, so that the default game plays exactly as earlier versions of Toybox did.
+    #[serde(default)]
+    pub swept_collisions: bool,
 }
 
 /// This data structure represents a Brick in the breakout game. Bricks are present in state even if they are destroyed, thus the presence of the "alive" boolean.
@@ -83,8 +88,8 @@ pub struct Brick {
 /// This struct contains the per-frame snapshot of mutable state in a Breakout game.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StateCore {
-    /// This random number generator is used to select the starting position and angle of the ball.
-    pub rand: random::Gen,
+    /// Random streams; "ball_start" selects the starting position and angle of the ball.
+    pub rand: random::Streams,
     /// Lives decrease every time the paddle misses the ball.
     pub lives: i32,
     /// The game does not proceed until the user presses the FIRE button to dispatch a new ball.
@@ -105,9 +110,13 @@ pub struct StateCore {
     pub bricks: Vec<Brick>,
     /// When set to true (from beating the level or dying), the bricks are reset to alive and a new ball is generated.
     pub reset: bool,
+    /// Brick hits and paddle bounces since the last State::drain_events; not saved in JSON.
+    #[serde(skip)]
+    pub events: EventLog,
 }
 
 /// The breakout game's true state has both the configuration that launched the game and information about the current frame.
+#[derive(Clone)]
 pub struct State {
     /// This contains information about the game that does not change during gameplay, but is referenced, read-only.
     pub config: Breakout,
@@ -1,10 +1,19 @@
-use toybox_core::graphics::{Color, Drawable};
-use toybox_core::{AleAction, Direction, Input, QueryError};
+use toybox_core::graphics::{unique_colors, Color, Drawable};
+use toybox_core::{
+    ActionSpace, AleAction, Direction, Event, EventLog, Input, LoadError, Object, QueryError,
+    StepResult,
+};
 
 use types::{DiagonalDir, FrameState, GridWorld, State, TileConfig};
 
 use serde_json;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+mod kinds {
+    pub const NAMES: &[&str] = &["player", "tile"];
+    pub const PLAYER: u8 = 1;
+    pub const TILE: u8 = 2;
+}
 
 impl TileConfig {
     fn wall() -> TileConfig {
@@ -51,7 +60,7 @@ impl TileConfig {
 
 impl Default for GridWorld {
     fn default() -> Self {
-        let mut tiles = HashMap::new();
+        let mut tiles = BTreeMap::new();
         tiles.insert('1', TileConfig::wall());
         tiles.insert('0', TileConfig::floor());
         tiles.insert('R', TileConfig::reward());
@@ -113,6 +122,7 @@ impl FrameState {
             tiles,
             grid,
             player: config.player_start,
+            events: EventLog::default(),
         }
     }
     fn get_tile(&self, tx: i32, ty: i32) -> Option<&TileConfig> {
@@ -163,6 +173,8 @@ impl FrameState {
         let reward = self.tiles[tile_id].reward;
         if reward != 0 {
             self.grid[y][x] = self.reward_becomes;
+            self.events
+                .push("reward", json!({ "reward": reward, "x": tx, "y": ty }));
         }
         reward
     }
@@ -173,6 +185,7 @@ impl FrameState {
         // check terminal before "collect_reward" which removes the reward from the map.
         if self.terminal(x, y) {
             self.game_over = true;
+            self.events.push("terminal", json!({ "x": x, "y": y }));
         }
 
         self.collect_reward(x, y);
@@ -181,6 +194,7 @@ impl FrameState {
 
 impl toybox_core::Simulation for GridWorld {
     fn reset_seed(&mut self, _seed: u32) {}
+    fn reset_seed_u64(&mut self, _seed: u64) {}
 
     /// Compute the size of the grid for determining how big the world should be.
     fn game_size(&self) -> (i32, i32) {
@@ -189,6 +203,10 @@ impl toybox_core::Simulation for GridWorld {
         (width, height)
     }
 
+    fn action_space(&self) -> ActionSpace {
+        ActionSpace::from_ale(&self.legal_action_set())
+    }
+
     fn legal_action_set(&self) -> Vec<AleAction> {
         let mut actions = vec![
             AleAction::NOOP,
@@ -197,10 +215,28 @@ impl toybox_core::Simulation for GridWorld {
             AleAction::UP,
             AleAction::DOWN,
         ];
+        if self.diagonal_support {
+            actions.extend(&[
+                AleAction::UPLEFT,
+                AleAction::UPRIGHT,
+                AleAction::DOWNLEFT,
+                AleAction::DOWNRIGHT,
+            ]);
+        }
         actions.sort();
         actions
     }
 
+    fn object_kinds(&self) -> Vec<String> {
+        kinds::NAMES.iter().map(|k| k.to_string()).collect()
+    }
+
+    fn palette(&self) -> Vec<Color> {
+        let mut colors = vec![Color::black(), self.player_color];
+        colors.extend(self.tiles.values().map(|tile| tile.color));
+        unique_colors(colors)
+    }
+
     fn new_game(&mut self) -> Box<toybox_core::State> {
         Box::new(State {
             frame: FrameState::from_config(&self),
@@ -208,10 +244,7 @@ impl toybox_core::Simulation for GridWorld {
         })
     }
 
-    fn new_state_from_json(
-        &self,
-        json_str: &str,
-    ) -> Result<Box<toybox_core::State>, serde_json::Error> {
+    fn new_state_from_json(&self, json_str: &str) -> Result<Box<toybox_core::State>, LoadError> {
         let state: State = serde_json::from_str(json_str)?;
         Ok(Box::new(state))
     }
@@ -220,10 +253,70 @@ impl toybox_core::Simulation for GridWorld {
         serde_json::to_string(self).expect("GridWorld should be JSON-serializable!")
     }
 
-    fn from_json(&self, json_str: &str) -> Result<Box<toybox_core::Simulation>, serde_json::Error> {
+    fn from_json(&self, json_str: &str) -> Result<Box<toybox_core::Simulation>, LoadError> {
         let config: GridWorld = serde_json::from_str(json_str)?;
         Ok(Box::new(config))
     }
+
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.grid.is_empty() {
+            problems.push("grid is empty.".to_owned());
+            return problems;
+        }
+        let width = self.grid[0].chars().count();
+        if width == 0 {
+            problems.push("grid rows are empty.".to_owned());
+        }
+        let mut missing = Vec::new();
+        for (y, row) in self.grid.iter().enumerate() {
+            let row_width = row.chars().count();
+            if row_width != width {
+                problems.push(format!(
+                    "grid row {} has width {} but the first row has width {}.",
+                    y, row_width, width
+                ));
+            }
+            for ch in row.chars() {
+                if !self.tiles.contains_key(&ch) && !missing.contains(&ch) {
+                    missing.push(ch);
+                }
+            }
+        }
+        for ch in missing {
+            problems.push(format!("grid uses {:?} but it is not in tiles.", ch));
+        }
+        if !self.tiles.contains_key(&self.reward_becomes) {
+            problems.push(format!(
+                "reward_becomes {:?} is not in tiles.",
+                self.reward_becomes
+            ));
+        }
+
+        let (px, py) = self.player_start;
+        let start_tile = self
+            .grid
+            .get(py as usize)
+            .and_then(|row| row.chars().nth(px as usize))
+            .filter(|_| px >= 0 && py >= 0);
+        match start_tile {
+            None => problems.push(format!(
+                "player_start ({}, {}) is outside the grid.",
+                px, py
+            )),
+            Some(ch) => {
+                if let Some(tile) = self.tiles.get(&ch) {
+                    if !tile.walkable {
+                        problems.push(format!(
+                            "player_start ({}, {}) is on {:?}, which is not walkable.",
+                            px, py, ch
+                        ));
+                    }
+                }
+            }
+        }
+        problems
+    }
 }
 
 impl DiagonalDir {
@@ -256,6 +349,7 @@ impl toybox_core::State for State {
     }
 
     fn update_mut(&mut self, buttons: Input) {
+        self.frame.events.next_frame();
         // Must take an action in GridWorld.
         if buttons.is_empty() {
             return;
@@ -282,6 +376,41 @@ impl toybox_core::State for State {
             }
         }
     }
+    fn step(&mut self, buttons: Input) -> StepResult {
+        let (score, lives) = (self.score(), self.lives());
+        self.update_mut(buttons);
+
+        let mut result = StepResult::diff(score, lives, self);
+        // GridWorld has no lives; it reports zero, rather than negative, lives once a terminal tile is reached.
+        result.life_lost = false;
+        result.game_over = self.frame.game_over;
+        result.events = self.frame.events.drain();
+        result
+    }
+    fn drain_events(&mut self) -> Vec<Event> {
+        self.frame.events.drain()
+    }
+    fn objects(&self) -> Vec<Object> {
+        let mut output = Vec::new();
+        let (px, py) = self.frame.player;
+        output.push(Object::new("player", px, py, 1, 1));
+
+        for (y, row) in self.frame.grid.iter().enumerate() {
+            for (x, &tile_id) in row.iter().enumerate() {
+                let tile = &self.frame.tiles[tile_id];
+                output.push(
+                    Object::new("tile", x as i32, y as i32, 1, 1).with_attributes(json!({
+                        "tile": tile_id,
+                        "reward": tile.reward,
+                        "walkable": tile.walkable,
+                        "terminal": tile.terminal,
+                    })),
+                );
+            }
+        }
+        output
+    }
+
     fn draw(&self) -> Vec<Drawable> {
         let mut output = Vec::new();
         output.push(Drawable::Clear(Color::black()));
@@ -290,23 +419,56 @@ impl toybox_core::State for State {
         for y in 0..height {
             for x in 0..width {
                 let tile = self.frame.get_tile(x, y).expect("Tile type should exist!");
-                output.push(Drawable::rect(tile.color, x as i32, y as i32, 1, 1));
+                // Tiles are numbered in reading order.
+                output.push(
+                    Drawable::rect(tile.color, x as i32, y as i32, 1, 1)
+                        .tagged(kinds::TILE, y * width + x + 1),
+                );
             }
         }
-        output.push(Drawable::rect(
-            self.config.player_color,
-            self.frame.player.0,
-            self.frame.player.1,
-            1,
-            1,
-        ));
+        output.push(
+            Drawable::rect(
+                self.config.player_color,
+                self.frame.player.0,
+                self.frame.player.1,
+                1,
+                1,
+            )
+            .tagged(kinds::PLAYER, 1),
+        );
 
         output
     }
+    /// The grid in the same characters as the config, with '@' for the player.
+    fn to_text(&self) -> String {
+        // Tile ids are indices into the config's tiles, in order.
+        let chars: Vec<char> = self.config.tiles.keys().cloned().collect();
+        let mut output = format!("score: {} step: {}\n", self.frame.score, self.frame.step);
+        for (y, row) in self.frame.grid.iter().enumerate() {
+            for (x, &tile_id) in row.iter().enumerate() {
+                if (x as i32, y as i32) == self.frame.player {
+                    output.push('@');
+                } else {
+                    output.push(chars.get(tile_id).cloned().unwrap_or('?'));
+                }
+            }
+            output.push('\n');
+        }
+        output
+    }
+
     fn to_json(&self) -> String {
         serde_json::to_string(self).expect("Should be no JSON Serialization Errors.")
     }
 
+    fn clone_box(&self) -> Box<toybox_core::State> {
+        Box::new(self.clone())
+    }
+
+    fn state_hash(&self) -> u64 {
+        toybox_core::stable_hash(self)
+    }
+
     fn query_json(&self, query: &str, _args: &serde_json::Value) -> Result<String, QueryError> {
         Ok(match query {
             "xy" => {
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use toybox_core::graphics::Color;
+use toybox_core::EventLog;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TileConfig {
@@ -17,7 +18,7 @@ pub struct TileConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GridWorld {
     pub grid: Vec<String>,
-    pub tiles: HashMap<char, TileConfig>,
+    pub tiles: BTreeMap<char, TileConfig>,
     pub reward_becomes: char,
     pub player_color: Color,
     pub player_start: (i32, i32),
@@ -39,6 +40,9 @@ pub struct FrameState {
     pub tiles: Vec<TileConfig>,
     pub grid: Vec<Vec<usize>>,
     pub player: (i32, i32),
+    /// Rewards collected and terminal tiles reached since the last State::drain_events; not saved in JSON.
+    #[serde(skip)]
+    pub events: EventLog,
 }
 
 /// Enumeration that supports diagonal movement.
@@ -12,11 +12,14 @@ pub enum FiringAI {
 // Note: using the RNG makes us mutable.
 fn target_player(state: &mut StateCore, config: &SpaceInvaders) -> u32 {
     let p = config.jitter;
-    let r: f64 = state.rand.gen();
+    let r: f64 = state.rand.stream("enemy_fire").gen();
     assert!(r >= 0. && r < 1.);
     if r < p {
         let active_ids = state.active_weapon_enemy_ids();
-        active_ids[state.rand.gen_range(0, active_ids.len()) as usize]
+        active_ids[state
+            .rand
+            .stream("enemy_fire")
+            .gen_range(0, active_ids.len()) as usize]
     } else {
         // Get active enemy closest to the player
         state.closest_enemy_id()
@@ -1,5 +1,6 @@
 extern crate itertools;
 extern crate serde;
+#[macro_use]
 extern crate serde_json;
 extern crate toybox_core;
 #[macro_use]
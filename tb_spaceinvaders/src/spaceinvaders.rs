@@ -6,9 +6,12 @@ use itertools::Itertools;
 use serde_json;
 use std::cmp::{max, min};
 use toybox_core::collision::Rect;
-use toybox_core::graphics::{Color, Drawable, FixedSpriteData, SpriteData};
+use toybox_core::graphics::{unique_colors, Color, Drawable, FixedSpriteData, SpriteData};
 use toybox_core::random;
-use toybox_core::{AleAction, Direction, Input, QueryError};
+use toybox_core::{
+    Action, ActionSpace, AleAction, Direction, Event, EventLog, Input, LoadError, Object,
+    QueryError, StepResult,
+};
 
 pub mod screen {
     pub const GAME_SIZE: (i32, i32) = (320, 210);
@@ -77,6 +80,15 @@ pub mod screen {
     pub const SHIELD_SPRITE_DATA: &str = include_str!("resources/space_invader_shield_x3");
 
 }
+
+mod kinds {
+    pub const NAMES: &[&str] = &["ship", "enemy", "laser", "shield", "ufo"];
+    pub const SHIP: u8 = 1;
+    pub const ENEMY: u8 = 2;
+    pub const LASER: u8 = 3;
+    pub const SHIELD: u8 = 4;
+    pub const UFO: u8 = 5;
+}
 lazy_static! {
     static ref INVADER_INIT_1: FixedSpriteData = load_sprite_default(
         include_str!("resources/space_invaders/invader_init_1"),
@@ -454,7 +466,7 @@ impl StateCore {
         let player_start_x = screen::SHIP_LIMIT_X1;
         let player_start_y = screen::SKY_TO_GROUND - screen::SHIP_SIZE.1;
         let mut state = StateCore {
-            rand: random::Gen::new_child(&mut config.rand),
+            rand: random::Streams::new(random::Gen::new_child(&mut config.rand)),
             life_display_timer: screen::NEW_LIFE_TIME,
             lives: 3,
             levels_completed: 0,
@@ -466,6 +478,7 @@ impl StateCore {
             enemies: Vec::new(),
             enemy_lasers: Vec::new(),
             ufo: Ufo::new(),
+            events: EventLog::default(),
         };
 
         state.reset_board(config);
@@ -581,6 +594,10 @@ impl StateCore {
             if enemy.death_counter.is_none() {
                 enemy.death_counter = Some(screen::DEATH_TIME)
             }
+            self.events.push(
+                "enemy_hit",
+                json!({ "id": enemy.id, "row": enemy.row, "col": enemy.col }),
+            );
         }
     }
 
@@ -604,6 +621,8 @@ impl StateCore {
                     if laser_rect.collides_visible(ufo.x, ufo.y, &sprite.data) {
                         ufo.start_death_counter();
                         self.score += screen::UFO_BONUS;
+                        self.events
+                            .push("ufo_hit", json!({ "x": ufo.x, "y": ufo.y }));
                     }
                 } else {
                     unreachable!("We should have exited earlier if the death counter is 0 and sprite is None.");
@@ -679,12 +698,13 @@ impl StateCore {
 
     fn laser_shield_check(&mut self, laser: &Rect) -> bool {
         // Check collision with living shields:
-        for shield in self.shields.iter_mut() {
+        for (i, shield) in self.shields.iter_mut().enumerate() {
             let shield_rect = Rect::new(shield.x, shield.y, shield.width(), shield.height());
 
             // Broad-phase collision: is it in the rectangle?
             if laser.intersects(&shield_rect) {
                 if destruction::destructive_collide(&laser, shield.x, shield.y, &mut shield.data) {
+                    self.events.push("shield_damage", json!({ "shield": i }));
                     return true;
                 }
             }
@@ -814,6 +834,9 @@ impl toybox_core::Simulation for SpaceInvaders {
     fn reset_seed(&mut self, seed: u32) {
         self.rand.reset_seed(seed)
     }
+    fn reset_seed_u64(&mut self, seed: u64) {
+        self.rand.reset_seed_u64(seed)
+    }
     fn game_size(&self) -> (i32, i32) {
         screen::GAME_SIZE
     }
@@ -823,6 +846,23 @@ impl toybox_core::Simulation for SpaceInvaders {
             state: StateCore::new(self),
         })
     }
+    /// Only one laser can be on screen at a time, so the fire button does nothing while it flies.
+    fn action_space(&self) -> ActionSpace {
+        let actions = self
+            .legal_action_set()
+            .into_iter()
+            .map(|a| {
+                let action = Action::from_ale(a);
+                if action.input.button1 {
+                    action.noop_when("laser_in_flight")
+                } else {
+                    action
+                }
+            })
+            .collect();
+        ActionSpace::new(actions)
+    }
+
     /// Sync with [ALE impl](https://github.com/mgbellemare/Arcade-Learning-Environment/blob/master/src/games/supported/SpaceInvaders.cpp#L85)
     /// Note, leaving a call to sort in this impl to remind users that these vecs are ordered!
     fn legal_action_set(&self) -> Vec<AleAction> {
@@ -837,10 +877,25 @@ impl toybox_core::Simulation for SpaceInvaders {
         actions.sort();
         actions
     }
-    fn new_state_from_json(
-        &self,
-        json_str: &str,
-    ) -> Result<Box<toybox_core::State>, serde_json::Error> {
+    fn object_kinds(&self) -> Vec<String> {
+        kinds::NAMES.iter().map(|k| k.to_string()).collect()
+    }
+    fn palette(&self) -> Vec<Color> {
+        let colors = &[
+            (0, 0, 0),
+            screen::LEFT_GAME_DOT_COLOR,
+            screen::RIGHT_GAME_DOT_COLOR,
+            screen::LIVES_DISPLAY_COLOR,
+            screen::SHIELD_COLOR,
+            screen::ENEMY_COLOR,
+            screen::UFO_COLOR,
+            screen::LASER_COLOR,
+            screen::GROUND_COLOR,
+            screen::SHIP_COLOR,
+        ];
+        unique_colors(colors.iter().map(Color::from))
+    }
+    fn new_state_from_json(&self, json_str: &str) -> Result<Box<toybox_core::State>, LoadError> {
         let state: StateCore = serde_json::from_str(json_str)?;
         Ok(Box::new(State {
             state,
@@ -852,10 +907,35 @@ impl toybox_core::Simulation for SpaceInvaders {
         serde_json::to_string(self).expect("SpaceInvaders should be JSON-serializable!")
     }
 
-    fn from_json(&self, json_str: &str) -> Result<Box<toybox_core::Simulation>, serde_json::Error> {
+    fn from_json(&self, json_str: &str) -> Result<Box<toybox_core::Simulation>, LoadError> {
         let config: SpaceInvaders = serde_json::from_str(json_str)?;
         Ok(Box::new(config))
     }
+
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.start_lives < 0 {
+            problems.push(format!("start_lives is negative: {}.", self.start_lives));
+        }
+        if self.jitter.is_nan() || self.jitter < 0.0 || self.jitter > 1.0 {
+            problems.push(format!(
+                "jitter is a probability and should be in [0, 1], not {}.",
+                self.jitter
+            ));
+        }
+        let (w, h) = (SHIELD_SPRITE.width(), SHIELD_SPRITE.height());
+        for (i, &(x, y)) in self.shields.iter().enumerate() {
+            let on_screen =
+                x >= 0 && y >= 0 && x + w <= screen::GAME_SIZE.0 && y + h <= screen::SKY_TO_GROUND;
+            if !on_screen {
+                problems.push(format!(
+                    "shields[{}] at ({}, {}) does not fit between the sky and the ground.",
+                    i, x, y
+                ));
+            }
+        }
+        problems
+    }
 }
 
 impl toybox_core::State for State {
@@ -866,6 +946,7 @@ impl toybox_core::State for State {
         self.state.score
     }
     fn update_mut(&mut self, buttons: Input) {
+        self.state.events.next_frame();
         if self.state.reset_condition() {
             // If enemies hit the earth, you have lost. Game is over.
             if self.state.has_lost() {
@@ -944,6 +1025,84 @@ impl toybox_core::State for State {
         self.state.player_toggle_death();
     }
 
+    fn step(&mut self, buttons: Input) -> StepResult {
+        let (score, lives) = (self.score(), self.lives());
+        let levels_completed = self.state.levels_completed;
+        self.update_mut(buttons);
+
+        let mut result = StepResult::diff(score, lives, self);
+        result.level_complete = self.state.levels_completed > levels_completed;
+        result.events = self.state.events.drain();
+        result
+    }
+
+    fn drain_events(&mut self) -> Vec<Event> {
+        self.state.events.drain()
+    }
+
+    fn objects(&self) -> Vec<Object> {
+        let mut output = Vec::new();
+        if self.lives() < 0 {
+            return output;
+        }
+
+        let ship = &self.state.ship;
+        if ship.alive {
+            output.push(Object::new("ship", ship.x, ship.y, ship.w, ship.h));
+        }
+
+        for enemy in self.state.enemies.iter().filter(|e| e.alive) {
+            let (w, h) = screen::ENEMY_SIZE;
+            output.push(
+                Object::new("enemy", enemy.x, enemy.y, w, h).with_attributes(json!({
+                    "id": enemy.id,
+                    "row": enemy.row,
+                    "col": enemy.col,
+                    "points": enemy.points,
+                })),
+            );
+        }
+
+        let lasers = self
+            .state
+            .ship_laser
+            .iter()
+            .map(|l| (l, "ship"))
+            .chain(self.state.enemy_lasers.iter().map(|l| (l, "enemy")));
+        for (laser, owner) in lasers {
+            let (dx, dy) = laser.movement.delta();
+            output.push(
+                Object::new("laser", laser.x, laser.y, laser.w, laser.h)
+                    .with_velocity(f64::from(dx * laser.speed), f64::from(dy * laser.speed))
+                    .with_attributes(json!({ "owner": owner })),
+            );
+        }
+
+        for (id, shield) in self.state.shields.iter().enumerate() {
+            output.push(
+                Object::new(
+                    "shield",
+                    shield.x,
+                    shield.y,
+                    shield.width(),
+                    shield.height(),
+                )
+                .with_attributes(json!({ "id": id })),
+            );
+        }
+
+        let ufo = &self.state.ufo;
+        if ufo.appearance_counter.is_none() && ufo.death_counter.is_none() {
+            let (w, h) = screen::UFO_SIZE;
+            output.push(
+                Object::new("ufo", ufo.x, ufo.y, w, h)
+                    .with_velocity(f64::from(screen::UFO_DELTA), 0.0),
+            );
+        }
+
+        output
+    }
+
     fn draw(&self) -> Vec<Drawable> {
         let mut output = Vec::new();
         output.push(Drawable::Clear(Color::black()));
@@ -973,11 +1132,10 @@ impl toybox_core::State for State {
         // draw score or mothership
         if self.state.ufo.appearance_counter.is_none() {
             if let Some(ufo_sprite) = get_ufo_sprite(&self.state.ufo) {
-                output.push(Drawable::sprite(
-                    self.state.ufo.x,
-                    self.state.ufo.y,
-                    ufo_sprite,
-                ));
+                output.push(
+                    Drawable::sprite(self.state.ufo.x, self.state.ufo.y, ufo_sprite)
+                        .tagged(kinds::UFO, 1),
+                );
             }
         } else {
             output.extend(draw_score(
@@ -1001,11 +1159,10 @@ impl toybox_core::State for State {
         if let Some(player_sprite) =
             get_player_sprite(&self.state.ship, self.state.life_display_timer)
         {
-            output.push(Drawable::sprite(
-                self.state.ship.x,
-                self.state.ship.y,
-                player_sprite.clone(),
-            ));
+            output.push(
+                Drawable::sprite(self.state.ship.x, self.state.ship.y, player_sprite.clone())
+                    .tagged(kinds::SHIP, 1),
+            );
         }
 
         // In between lives.
@@ -1017,49 +1174,88 @@ impl toybox_core::State for State {
             ));
         }
 
-        for shield in &self.state.shields {
-            output.push(Drawable::DestructibleSprite(shield.clone()));
+        for (i, shield) in self.state.shields.iter().enumerate() {
+            output.push(
+                Drawable::DestructibleSprite(shield.clone()).tagged(kinds::SHIELD, i as i32 + 1),
+            );
         }
 
         for enemy in self.state.enemies.iter().filter(|e| e.alive) {
-            output.push(Drawable::sprite(
-                enemy.x,
-                enemy.y,
-                get_invader_sprite(&enemy),
-            ));
+            output.push(
+                Drawable::sprite(enemy.x, enemy.y, get_invader_sprite(&enemy))
+                    .tagged(kinds::ENEMY, enemy.id as i32 + 1),
+            );
         }
 
+        // The ship's laser is instance 1; enemy lasers follow.
         if let Some(ref laser) = self.state.ship_laser {
             if laser.is_visible() {
-                output.push(Drawable::rect(
-                    laser.color,
-                    laser.x,
-                    laser.y,
-                    laser.w,
-                    laser.h,
-                ))
+                output.push(
+                    Drawable::rect(laser.color, laser.x, laser.y, laser.w, laser.h)
+                        .tagged(kinds::LASER, 1),
+                )
             }
         }
 
-        for laser in &self.state.enemy_lasers {
+        for (i, laser) in self.state.enemy_lasers.iter().enumerate() {
             if laser.is_visible() {
-                output.push(Drawable::rect(
-                    laser.color,
-                    laser.x,
-                    laser.y,
-                    laser.w,
-                    laser.h,
-                ));
+                output.push(
+                    Drawable::rect(laser.color, laser.x, laser.y, laser.w, laser.h)
+                        .tagged(kinds::LASER, i as i32 + 2),
+                );
             }
         }
 
         output
     }
 
+    /// Enemies by row and column ('W' alive, '.' gone), then the ship, shots and mothership.
+    fn to_text(&self) -> String {
+        let state = &self.state;
+        let rows = state.enemies.iter().map(|e| e.row + 1).max().unwrap_or(0);
+        let cols = state.enemies.iter().map(|e| e.col + 1).max().unwrap_or(0);
+        let mut grid = vec![vec![' '; cols as usize]; rows as usize];
+        for enemy in &state.enemies {
+            grid[enemy.row as usize][enemy.col as usize] = if enemy.alive { 'W' } else { '.' };
+        }
+
+        let mut output = format!(
+            "score: {} lives: {} levels completed: {}\n",
+            state.score, state.lives, state.levels_completed
+        );
+        for row in grid {
+            output.extend(row);
+            output.push('\n');
+        }
+        output.push_str(&format!(
+            "ship: x={} {}\n",
+            state.ship.x,
+            if state.ship.alive { "alive" } else { "dead" }
+        ));
+        if let Some(ref laser) = state.ship_laser {
+            output.push_str(&format!("ship laser: ({}, {})\n", laser.x, laser.y));
+        }
+        for laser in &state.enemy_lasers {
+            output.push_str(&format!("enemy laser: ({}, {})\n", laser.x, laser.y));
+        }
+        if state.ufo.appearance_counter.is_none() {
+            output.push_str(&format!("ufo: x={}\n", state.ufo.x));
+        }
+        output
+    }
+
     fn to_json(&self) -> String {
         serde_json::to_string(&self.state).expect("Should be no JSON Serialization Errors.")
     }
 
+    fn clone_box(&self) -> Box<toybox_core::State> {
+        Box::new(self.clone())
+    }
+
+    fn state_hash(&self) -> u64 {
+        toybox_core::stable_hash(&self.state)
+    }
+
     fn query_json(&self, query: &str, _args: &serde_json::Value) -> Result<String, QueryError> {
         let _config = &self.config;
         let state = &self.state;
@@ -1082,4 +1278,14 @@ mod tests {
         assert_eq!(super::screen::SHIELD_SIZE.1, sprite.height());
     }
 
+    #[test]
+    pub fn test_validate() {
+        use toybox_core::Simulation;
+        assert!(super::SpaceInvaders::default().validate().is_empty());
+
+        let mut config = super::SpaceInvaders::default();
+        config.jitter = 1.5;
+        config.shields.push((310, 10));
+        assert_eq!(config.validate().len(), 2);
+    }
 }
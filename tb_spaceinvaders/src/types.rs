@@ -3,6 +3,7 @@ use serde::{Deserialize, Serialize};
 use toybox_core::graphics::{Color, SpriteData};
 use toybox_core::random;
 use toybox_core::Direction;
+use toybox_core::EventLog;
 
 /// The player's ship is represented by this structure.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -106,8 +107,8 @@ pub struct SpaceInvaders {
 /// This struct contains the state of Space Invaders; everything that can change from frame to frame is represented.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct StateCore {
-    /// This random number generator is used for firing behavior.
-    pub rand: random::Gen,
+    /// Random streams; "enemy_fire" is used for firing behavior.
+    pub rand: random::Streams,
     /// This is an animation timer; lives are shown before the level begins.
     pub life_display_timer: i32,
     /// How many lives are remaining?
@@ -131,9 +132,14 @@ pub struct StateCore {
 
     /// Mothership
     pub ufo: Ufo,
+
+    /// Enemy hits and shield damage since the last State::drain_events; not saved in JSON.
+    #[serde(skip)]
+    pub events: EventLog,
 }
 
 /// The unified state of SpaceInvaders contains both the config (read-only) and the frame state.
+#[derive(Clone)]
 pub struct State {
     /// Constant configuration available to game logic.
     pub config: SpaceInvaders,
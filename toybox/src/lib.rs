@@ -2,13 +2,15 @@ extern crate toybox_core;
 
 pub use toybox_core::graphics;
 pub use toybox_core::random;
+pub use toybox_core::replay;
 /// Input represents the buttons pressed given to our games.
 pub use toybox_core::Input;
+pub use toybox_core::LoadError;
 pub use toybox_core::Simulation;
 pub use toybox_core::State;
 
 /// This method returns a Box<Simulation> if possible for a given game name.
-pub fn get_simulation_by_name(name: &str) -> Result<Box<Simulation>, String> {
+pub fn get_simulation_by_name(name: &str) -> Result<Box<Simulation>, LoadError> {
     let y: Result<Box<Simulation>, _> = match name.to_lowercase().as_str() {
         #[cfg(feature = "amidar")]
         "amidar" => Ok(Box::new(amidar::Amidar::default())),
@@ -18,10 +20,10 @@ pub fn get_simulation_by_name(name: &str) -> Result<Box<Simulation>, String> {
         "space_invaders" => Ok(Box::new(space_invaders::SpaceInvaders::default())),
         #[cfg(feature = "gridworld")]
         "gridworld" => Ok(Box::new(gridworld::GridWorld::default())),
-        _ => Err(format!(
-            "Cannot construct game: `{}`. Try any of {:?}.",
-            name, GAME_LIST
-        )),
+        _ => Err(LoadError::UnknownGame {
+            name: name.to_owned(),
+            known: GAME_LIST.iter().map(|g| g.to_string()).collect(),
+        }),
     };
     y
 }
@@ -50,3 +52,282 @@ extern crate gridworld;
 /// Space Invaders logic defined in this module.
 #[cfg(feature = "space_invaders")]
 extern crate space_invaders;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toybox_core::replay::{ReplayError, ReplayRecorder};
+    use toybox_core::AleAction;
+
+    #[test]
+    fn test_unknown_game() {
+        let error = get_simulation_by_name("pong").err().unwrap();
+        assert_eq!(error.code(), 3);
+        assert_eq!(
+            error.to_string(),
+            format!("Unknown game: `pong`. Try any of {:?}.", GAME_LIST)
+        );
+    }
+
+    #[test]
+    fn test_clone_and_hash_every_game() {
+        for name in GAME_LIST {
+            let mut sim = get_simulation_by_name(name).unwrap();
+            let actions = sim.legal_action_set();
+            let state = sim.new_game();
+
+            let mut copy = state.clone();
+            assert_eq!(state.state_hash(), copy.state_hash(), "{}", name);
+            let reloaded = sim.new_state_from_json(&state.to_json()).unwrap();
+            assert_eq!(state.state_hash(), reloaded.state_hash(), "{}", name);
+
+            let before = state.to_json();
+            for i in 0..100 {
+                copy.update_mut(actions[i % actions.len()].to_input());
+            }
+            assert_eq!(before, state.to_json(), "{}", name);
+            assert_ne!(state.state_hash(), copy.state_hash(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_step_results_every_game() {
+        for name in GAME_LIST {
+            let mut sim = get_simulation_by_name(name).unwrap();
+            let actions = sim.legal_action_set();
+            let mut state = sim.new_game();
+            let start_score = state.score();
+            let mut total_reward = 0;
+            for i in 0..2000 {
+                let result = state.step(actions[(i * 5 + i / 11) % actions.len()].to_input());
+                total_reward += result.reward;
+                // Stepping hands over this step's events and leaves nothing behind in the log.
+                for event in &result.events {
+                    assert_eq!(event.frame, i as u64 + 1, "{}", name);
+                }
+                assert!(state.drain_events().is_empty(), "{}", name);
+                if result.game_over {
+                    break;
+                }
+            }
+            assert_eq!(state.score() - start_score, total_reward, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_events_every_game() {
+        for name in GAME_LIST {
+            let mut sim = get_simulation_by_name(name).unwrap();
+            let actions = sim.legal_action_set();
+            let mut state = sim.new_game();
+            for i in 0..2000 {
+                if state.lives() < 0 {
+                    break;
+                }
+                state.update_mut(actions[(i * 5 + i / 11) % actions.len()].to_input());
+                // Every event is stamped with the update_mut call that logged it.
+                for event in state.drain_events() {
+                    assert_eq!(event.frame, i as u64 + 1, "{}", name);
+                }
+                assert!(state.drain_events().is_empty(), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_objects_every_game() {
+        for name in GAME_LIST {
+            let mut sim = get_simulation_by_name(name).unwrap();
+            let actions = sim.legal_action_set();
+            let kinds = sim.object_kinds();
+            let mut state = sim.new_game();
+            for i in 0..300 {
+                if state.lives() < 0 {
+                    break;
+                }
+                let objects = state.objects();
+                assert!(!objects.is_empty(), "{}", name);
+                for obj in &objects {
+                    assert!(kinds.contains(&obj.kind), "{}: {:?}", name, obj.kind);
+                    assert!(obj.w > 0 && obj.h > 0, "{}: {:?}", name, obj);
+                }
+                let tensor = toybox_core::encode_objects(&objects, &kinds, 8);
+                assert_eq!(
+                    tensor.len(),
+                    8 * toybox_core::object_row_width(kinds.len()),
+                    "{}",
+                    name
+                );
+                state.update_mut(actions[(i * 3 + i / 7) % actions.len()].to_input());
+            }
+        }
+    }
+
+    #[test]
+    fn test_masks_every_game() {
+        for name in GAME_LIST {
+            let mut sim = get_simulation_by_name(name).unwrap();
+            let num_kinds = sim.object_kinds().len();
+            let (w, h) = sim.game_size();
+            let state = sim.new_game();
+
+            let mut mask = graphics::MaskBuffer::alloc(w, h);
+            mask.render(&state.draw());
+            assert!(mask.class_ids.iter().any(|&c| c > 0), "{}", name);
+            assert!(
+                mask.class_ids.iter().all(|&c| (c as usize) <= num_kinds),
+                "{}",
+                name
+            );
+            for (&c, &i) in mask.class_ids.iter().zip(mask.instance_ids.iter()) {
+                assert_eq!(c == 0, i == 0, "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_text_every_game() {
+        for name in GAME_LIST {
+            let mut sim = get_simulation_by_name(name).unwrap();
+            let size = sim.game_size();
+            let state = sim.new_game();
+
+            let text = state.to_text();
+            assert!(text.lines().count() > 1, "{}", name);
+            let ascii = graphics::render_text(&state.draw(), size, 40, graphics::TextStyle::Ascii);
+            assert!(ascii.lines().all(|line| line.len() == 40), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_action_space_every_game() {
+        for name in GAME_LIST {
+            let sim = get_simulation_by_name(name).unwrap();
+            let legal = sim.legal_action_set();
+            let space = sim.action_space();
+            assert_eq!(space.len(), legal.len(), "{}", name);
+            for (action, ale) in space.actions.iter().zip(legal.iter()) {
+                assert_eq!(action.input, ale.to_input(), "{} {}", name, action.name);
+                assert_eq!(
+                    space.index_of_name(&action.name),
+                    space.index_of(action.input)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_action_space_combined_fire() {
+        // Moving while firing presses the same button, so it is wasted in the same situations.
+        for name in GAME_LIST {
+            let space = get_simulation_by_name(name).unwrap().action_space();
+            let fire = match space.index_of(AleAction::FIRE.to_input()) {
+                Some(i) => &space.actions[i],
+                None => continue,
+            };
+            for action in space.actions.iter().filter(|a| a.input.button1) {
+                assert_eq!(action.noop_when, fire.noop_when, "{} {}", name, action.name);
+            }
+        }
+        let amidar = get_simulation_by_name("amidar").unwrap().action_space();
+        let up_jump = &amidar.actions[amidar.index_of_name("up_jump").unwrap()];
+        assert!(up_jump.noop_when.contains(&"no_jumps_left".to_owned()));
+        let invaders = get_simulation_by_name("space_invaders")
+            .unwrap()
+            .action_space();
+        let left_fire = &invaders.actions[invaders.index_of_name("left_fire").unwrap()];
+        assert_eq!(left_fire.noop_when, vec!["laser_in_flight".to_owned()]);
+    }
+
+    #[test]
+    fn test_action_space_follows_config() {
+        let amidar = get_simulation_by_name("amidar").unwrap().action_space();
+        assert!(amidar.index_of_name("jump").is_some());
+        assert!(amidar.index_of_name("fire").is_none());
+
+        let gridworld = get_simulation_by_name("gridworld").unwrap();
+        assert!(gridworld.action_space().index_of_name("up_left").is_none());
+        let config = gridworld
+            .to_json()
+            .replace("\"diagonal_support\":false", "\"diagonal_support\":true");
+        let diagonal = gridworld.from_json(&config).unwrap();
+        let space = diagonal.action_space();
+        assert_eq!(space.len(), 9);
+        assert!(space.index_of_name("up_left").is_some());
+    }
+
+    #[test]
+    fn test_palette_every_game() {
+        for name in GAME_LIST {
+            let mut sim = get_simulation_by_name(name).unwrap();
+            let palette = sim.palette();
+            let (w, h) = sim.game_size();
+            let actions = sim.legal_action_set();
+            let mut state = sim.new_game();
+
+            // Every color drawn, after some play, is already in the palette.
+            let mut buffer = graphics::PaletteBuffer::alloc(w, h, palette.clone());
+            for i in 0..300 {
+                if state.lives() < 0 {
+                    break;
+                }
+                state.update_mut(actions[i % actions.len()].to_input());
+                buffer.render(&state.draw());
+                assert_eq!(buffer.palette, palette, "{} step {}", name, i);
+            }
+        }
+    }
+
+    #[test]
+    fn test_replay_every_game() {
+        for name in GAME_LIST {
+            let mut sim = get_simulation_by_name(name).unwrap();
+            let actions = sim.legal_action_set();
+            let mut state = sim.new_game();
+            let mut recorder = ReplayRecorder::new(sim.as_ref(), state.as_ref(), 50);
+            for i in 0..500 {
+                if state.lives() < 0 {
+                    break;
+                }
+                let action: AleAction = actions[(i * 7 + i / 13) % actions.len()];
+                recorder.step(state.as_mut(), action.to_input());
+            }
+            let replay = recorder.replay();
+            let replay = replay::Replay::from_json(&replay.to_json()).unwrap();
+
+            let fresh = get_simulation_by_name(name).unwrap();
+            let replayed = replay.play(fresh.as_ref()).unwrap();
+            assert_eq!(state.state_hash(), replayed.state_hash(), "{}", name);
+
+            let mut tampered = replay.clone();
+            tampered.checkpoints.last_mut().unwrap().score += 1;
+            match tampered.play(fresh.as_ref()) {
+                Err(ReplayError::Diverged { .. }) => {}
+                _ => panic!("{}: tampered replay should diverge", name),
+            }
+        }
+    }
+
+    #[test]
+    fn test_replay_breakout_paddle() {
+        let mut sim = get_simulation_by_name("breakout").unwrap();
+        let mut state = sim.new_game();
+        let mut recorder = ReplayRecorder::new(sim.as_ref(), state.as_ref(), 10);
+        for i in 0..300 {
+            let mut input = AleAction::FIRE.to_input();
+            // Buttons only at first, then sweep the paddle controller back and forth.
+            if i >= 20 {
+                input.paddle = Some((i % 50) as f32 / 50.0);
+            }
+            recorder.step(state.as_mut(), input);
+        }
+        let replay = replay::Replay::from_json(&recorder.replay().to_json()).unwrap();
+        assert_eq!(replay.paddle.len(), replay.len());
+        assert_eq!(replay.paddle[19], None);
+        assert_eq!(replay.paddle[20], Some(0.4));
+
+        let fresh = get_simulation_by_name("breakout").unwrap();
+        let replayed = replay.play(fresh.as_ref()).unwrap();
+        assert_eq!(state.state_hash(), replayed.state_hash());
+    }
+}